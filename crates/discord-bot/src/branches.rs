@@ -0,0 +1,154 @@
+//! Picking which branches to track, and in what order
+use std::{cmp::Reverse, collections::BTreeSet};
+
+use glob::Pattern;
+
+/// A stable release, as its `(year, month)`
+pub type Release = (u8, u8);
+
+/// Check if a branch from our configuration is a pattern rather than an exact name
+pub fn is_pattern(branch: &str) -> bool {
+	branch.contains(['*', '?', '[', ']'])
+}
+
+/// Find the stable release a branch belongs to (i.e., `24.05` for `nixos-24.05-small`)
+pub fn release(branch: &str) -> Option<Release> {
+	branch.split('-').find_map(|part| {
+		let (year, month) = part.split_once('.')?;
+		if year.len() != 2 || month.len() != 2 {
+			return None;
+		}
+
+		Some((year.parse().ok()?, month.parse().ok()?))
+	})
+}
+
+/// Keep the `branches` matching any of `patterns`, in the same order
+pub fn matching(patterns: &[String], branches: Vec<String>) -> Vec<String> {
+	let compiled: Vec<_> = patterns
+		.iter()
+		.filter_map(|pattern| Pattern::new(pattern).ok())
+		.collect();
+
+	branches
+		.into_iter()
+		.filter(|branch| compiled.iter().any(|pattern| pattern.matches(branch)))
+		.collect()
+}
+
+/// Drop the `branches` of any stable releases older than the newest `count`
+///
+/// Branches that aren't part of a release are always kept
+pub fn newest_releases(mut branches: Vec<String>, count: usize) -> Vec<String> {
+	let releases: BTreeSet<_> = branches
+		.iter()
+		.filter_map(|branch| release(branch))
+		.collect();
+	let kept: Vec<_> = releases.into_iter().rev().take(count).collect();
+	branches.retain(|branch| release(branch).is_none_or(|release| kept.contains(&release)));
+
+	branches
+}
+
+/// Pick which of the `available` branches to track
+///
+/// Branches are ordered by the first of our `patterns` that matches them, then by newest
+/// release. When `stable_releases` is set, branches of any older releases are dropped
+pub fn select(
+	patterns: &[String],
+	available: Vec<String>,
+	stable_releases: Option<usize>,
+) -> Vec<String> {
+	let compiled: Vec<_> = patterns
+		.iter()
+		.filter_map(|pattern| Pattern::new(pattern).ok())
+		.collect();
+
+	let mut branches = available;
+	if let Some(count) = stable_releases {
+		branches = newest_releases(branches, count);
+	}
+
+	branches.sort_by_cached_key(|branch| {
+		let position = compiled
+			.iter()
+			.position(|pattern| pattern.matches(branch))
+			.unwrap_or(usize::MAX);
+		(position, Reverse(release(branch)), branch.clone())
+	});
+
+	branches
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{is_pattern, newest_releases, release, select};
+
+	fn strings(branches: &[&str]) -> Vec<String> {
+		branches.iter().map(ToString::to_string).collect()
+	}
+
+	#[test]
+	fn finds_releases() {
+		assert_eq!(release("nixos-24.05-small"), Some((24, 5)));
+		assert_eq!(release("nixpkgs-23.11-darwin"), Some((23, 11)));
+		assert_eq!(release("release-24.05"), Some((24, 5)));
+		assert_eq!(release("nixos-unstable"), None);
+		assert_eq!(release("release-2024.05"), None);
+		assert!(is_pattern("nixos-*"));
+		assert!(!is_pattern("nixos-unstable"));
+	}
+
+	#[test]
+	fn keeps_only_the_newest_releases() {
+		let branches = strings(&[
+			"master",
+			"release-23.11",
+			"nixos-24.05",
+			"release-24.05",
+			"release-24.11",
+		]);
+		assert_eq!(
+			newest_releases(branches.clone(), 2),
+			strings(&["master", "nixos-24.05", "release-24.05", "release-24.11"])
+		);
+		assert_eq!(newest_releases(branches, 0), strings(&["master"]));
+	}
+
+	#[test]
+	fn orders_by_pattern_then_newest_release() {
+		let patterns = strings(&["master", "nixos-*", "release-*"]);
+		let available = strings(&[
+			"release-23.11",
+			"nixos-23.11",
+			"release-24.05",
+			"nixos-unstable",
+			"nixos-24.05",
+			"staging",
+			"master",
+		]);
+
+		assert_eq!(
+			select(&patterns, available.clone(), None),
+			strings(&[
+				"master",
+				"nixos-24.05",
+				"nixos-23.11",
+				"nixos-unstable",
+				"release-24.05",
+				"release-23.11",
+				"staging",
+			])
+		);
+		assert_eq!(
+			select(&patterns, available, Some(1)),
+			strings(&[
+				"master",
+				"nixos-24.05",
+				"nixos-unstable",
+				"release-24.05",
+				"staging",
+			])
+		);
+	}
+}
@@ -34,7 +34,7 @@ where
 		embed = embed.image(teawie_url).footer(footer);
 	} else if let Some(error) = random_teawie.error {
 		warn!("Error from TeawieAPI: {error:#?}");
-	};
+	}
 
 	let message = CreateInteractionResponseMessage::new().embed(embed);
 	let response = CreateInteractionResponse::Message(message);
@@ -0,0 +1,240 @@
+use crate::{
+	branches,
+	config::Config,
+	pipeline::{Pipeline, Pipelines},
+	preferences,
+	storage::Storage,
+};
+
+use std::collections::HashMap;
+
+use eyre::Result;
+use git_tracker::Tip;
+use serenity::builder::{
+	CreateCommand, CreateEmbed, CreateEmbedFooter, CreateInteractionResponseFollowup,
+};
+use serenity::model::{
+	application::{CommandInteraction, InstallationContext},
+	colour::Colour,
+	Timestamp,
+};
+use serenity::prelude::Context;
+
+const DAY_SECS: i64 = 60 * 60 * 24;
+
+/// How long branches of the unstable pipeline can go without advancing before we point it out.
+/// Its channels usually advance every day or two
+const UNSTABLE_STALE_AFTER_SECS: i64 = DAY_SECS * 3;
+
+/// How long branches of a maintained release can go without advancing before we point it out
+const STABLE_STALE_AFTER_SECS: i64 = DAY_SECS * 7;
+
+/// How many of the newest releases are maintained. The previous release is supported for a
+/// while after a new one comes out, and older ones never advance again
+const MAINTAINED_RELEASES: usize = 2;
+
+/// Characters we allow ourselves in the embed. Discord's limit is 6000, and leaves some room
+/// for the title and footer
+const EMBED_BUDGET: usize = 5500;
+
+/// Most fields Discord allows in an embed
+const MAX_FIELDS: usize = 25;
+
+/// Most characters Discord allows in a field's value
+const MAX_FIELD_VALUE: usize = 1024;
+
+/// Most characters Discord allows in an embed's description
+const MAX_DESCRIPTION: usize = 4096;
+
+/// Room we leave for saying how many items didn't fit
+const MORE_MARKER_ROOM: usize = 32;
+
+pub async fn respond(
+	ctx: &Context,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()> {
+	command.defer(&ctx).await?;
+
+	let tracked = config.nixpkgs_branches();
+	let mut branches = tracked.clone();
+	if let Some(patterns) = preferences::lookup(storage, command.guild_id, command.user.id)? {
+		branches = branches::matching(&patterns, branches);
+	}
+
+	let tips: HashMap<_, _> = config
+		.repository()
+		.branch_tips(branches.clone())
+		.await?
+		.into_iter()
+		.map(|tip| (tip.branch.clone(), tip))
+		.collect();
+	if tips.is_empty() {
+		let resp = CreateInteractionResponseFollowup::new()
+			.content("I don't have any branches yet! I might still be cloning nixpkgs");
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	}
+
+	let last_seen = storage.lock().branch_advances().all()?;
+	let now = Timestamp::now().unix_timestamp();
+	let maintained = maintained_releases(config.pipelines(), &tracked);
+
+	// one field per pipeline, and one for everything else
+	let pipelines = config.pipelines().for_branches(&branches);
+	let loose = Pipeline::flat(
+		"Other".to_string(),
+		branches
+			.iter()
+			.filter(|branch| !pipelines.iter().any(|pipeline| pipeline.contains(branch)))
+			.cloned()
+			.collect(),
+	);
+
+	let mut stale = vec![];
+	let mut fields = vec![];
+	for pipeline in pipelines.iter().chain(std::iter::once(&loose)) {
+		let stale_after = if pipeline.name == loose.name {
+			None
+		} else if pipeline.name == "unstable" {
+			Some(UNSTABLE_STALE_AFTER_SECS)
+		} else if maintained.contains(&pipeline.name) {
+			Some(STABLE_STALE_AFTER_SECS)
+		} else {
+			// nobody expects an end-of-life release to move
+			None
+		};
+
+		let mut lines = vec![];
+		for tip in pipeline.stages.iter().filter_map(|stage| tips.get(stage)) {
+			let seen_at = last_seen.get(&tip.branch).copied();
+			// channels point to commits from a while before they advance, so prefer when we saw
+			// them move
+			let idle = now - seen_at.unwrap_or(tip.time.seconds());
+			let is_stale = stale_after.is_some_and(|stale_after| idle > stale_after);
+			if is_stale {
+				stale.push(format!("`{}`", tip.branch));
+			}
+
+			lines.push(tip_line(tip, seen_at, is_stale));
+		}
+		if !lines.is_empty() {
+			fields.push((&pipeline.name, lines));
+		}
+	}
+
+	let mut embed = CreateEmbed::new().title("Nixpkgs Channels");
+	let mut used = 0;
+	if !stale.is_empty() {
+		let warning =
+			" haven't moved in longer than usual, so fixes might take a while to reach them";
+		let description = format!(
+			"⚠️ {}{warning}",
+			truncated(&stale, ", ", MAX_DESCRIPTION - warning.len() - "⚠️ ".len())
+		);
+
+		used += description.len();
+		embed = embed.description(description).color(Colour::ORANGE);
+	}
+
+	let mut shown = 0;
+	let mut hidden = 0;
+	for (name, lines) in fields {
+		let value = truncated(&lines, "\n", MAX_FIELD_VALUE);
+		if shown == MAX_FIELDS || used + name.len() + value.len() > EMBED_BUDGET {
+			hidden += lines.len();
+			continue;
+		}
+
+		used += name.len() + value.len();
+		shown += 1;
+		embed = embed.field(name, value, false);
+	}
+
+	if hidden > 0 {
+		embed = embed.footer(CreateEmbedFooter::new(format!(
+			"{hidden} more branches didn't fit. Try `/config branches` to show fewer"
+		)));
+	}
+
+	let resp = CreateInteractionResponseFollowup::new().embed(embed);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Find the names of the release pipelines that are still maintained (i.e., `24.05`)
+fn maintained_releases(pipelines: &Pipelines, tracked: &[String]) -> Vec<String> {
+	pipelines
+		.for_branches(tracked)
+		.into_iter()
+		.filter(|pipeline| pipeline.name != "unstable")
+		.take(MAINTAINED_RELEASES)
+		.map(|pipeline| pipeline.name)
+		.collect()
+}
+
+/// Join as many `items` as fit in `limit` characters, ending with how many more there are if
+/// some didn't
+fn truncated(items: &[String], separator: &str, limit: usize) -> String {
+	let joined = items.join(separator);
+	if joined.len() <= limit {
+		return joined;
+	}
+
+	let mut truncated = String::new();
+	let mut count = 0;
+	for item in items {
+		if truncated.len() + item.len() + separator.len() + MORE_MARKER_ROOM > limit {
+			break;
+		}
+
+		truncated.push_str(item);
+		truncated.push_str(separator);
+		count += 1;
+	}
+
+	format!("{truncated}…and {} more", items.len() - count)
+}
+
+/// Describe where a branch is, and when we last saw it move
+fn tip_line(tip: &Tip, seen_at: Option<i64>, is_stale: bool) -> String {
+	let warning = if is_stale { " ⚠️" } else { "" };
+	let advanced = seen_at.map_or_else(
+		|| "not seen advancing".to_string(),
+		|seen_at| format!("advanced <t:{seen_at}:R>"),
+	);
+
+	format!(
+		"`{}`{warning} `{}` from <t:{}:R>, {advanced}",
+		tip.branch,
+		&tip.commit.to_string()[..12],
+		tip.time.seconds()
+	)
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("channels")
+		.description("See where each branch I track is, and when it last advanced")
+		.add_integration_type(InstallationContext::User)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{truncated, MORE_MARKER_ROOM};
+
+	#[test]
+	fn says_how_many_items_didnt_fit() {
+		let items: Vec<_> = (0..10).map(|index| format!("item {index}")).collect();
+		assert_eq!(truncated(&items[..2], ", ", 100), "item 0, item 1");
+
+		let limit = MORE_MARKER_ROOM + "item 0, item 1, ".len();
+		let value = truncated(&items, ", ", limit);
+		assert_eq!(value, "item 0, item 1, …and 8 more");
+		assert!(value.len() <= limit);
+
+		assert_eq!(truncated(&items, "\n", MORE_MARKER_ROOM), "…and 10 more");
+	}
+}
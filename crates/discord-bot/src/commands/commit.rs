@@ -0,0 +1,75 @@
+use crate::{config::Config, http::GitHubClientExt, storage::Storage};
+
+use std::sync::Arc;
+
+use eyre::Result;
+use serenity::builder::{CreateCommand, CreateCommandOption, CreateInteractionResponseFollowup};
+use serenity::model::application::{CommandInteraction, CommandOptionType, InstallationContext};
+use serenity::prelude::Context;
+
+pub async fn respond<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	// this will probably take a while
+	command.defer(&ctx).await?;
+
+	let Some(super::Lookup { sha, merge }) = super::look_up_commit(ctx, config, command).await?
+	else {
+		return Ok(());
+	};
+
+	// find out which PR brought the commit in
+	let Some(merge) = merge else {
+		let resp = CreateInteractionResponseFollowup::new()
+			.content(format!("I can't find `{sha}` on any branch I track"));
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	};
+
+	let Some(number) = merge.pull_request else {
+		let resp = CreateInteractionResponseFollowup::new().content(format!(
+			"`{sha}` landed on `{}` <t:{}:R> in `{}`, but not through a PR I can find",
+			merge.branch,
+			merge.landing.time.seconds(),
+			merge.landing.commit
+		));
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	};
+
+	let pull_request = super::pull_request(http, config, storage, number).await?;
+	let context = format!(
+		"`{}` was merged into `{}` by PR #{number}",
+		&sha[..12],
+		merge.branch
+	);
+	super::track::respond_with_status(
+		ctx,
+		http,
+		config,
+		storage,
+		command,
+		pull_request,
+		Some(context),
+	)
+	.await
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("commit")
+		.description("Find the nixpkgs PR a commit came from, and where it has reached")
+		.add_integration_type(InstallationContext::User)
+		.add_option(
+			CreateCommandOption::new(CommandOptionType::String, "sha", "SHA of the commit")
+				.required(true),
+		)
+}
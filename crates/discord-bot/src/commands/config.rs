@@ -0,0 +1,214 @@
+use crate::{branches, config::Config, preferences::Owner, storage::Storage};
+
+use eyre::Result;
+use glob::Pattern;
+use serenity::builder::{CreateCommand, CreateCommandOption, CreateInteractionResponseFollowup};
+use serenity::model::{
+	application::{
+		CommandInteraction, CommandOptionType, InstallationContext, ResolvedOption, ResolvedValue,
+	},
+	Permissions,
+};
+use serenity::prelude::Context;
+
+pub async fn respond(
+	ctx: &Context,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()> {
+	command.defer_ephemeral(&ctx).await?;
+
+	let options = command.data.options();
+	let Some(ResolvedOption {
+		name: "branches",
+		value: ResolvedValue::SubCommandGroup(subcommands),
+		..
+	}) = options.first()
+	else {
+		let resp = CreateInteractionResponseFollowup::new().content("I don't know that setting!");
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	};
+
+	let mut patterns = "";
+	let mut scope = "me";
+	let subcommand = subcommands.first().map(|subcommand| {
+		if let ResolvedValue::SubCommand(options) = &subcommand.value {
+			for option in options {
+				match (option.name, &option.value) {
+					("branches", ResolvedValue::String(value)) => patterns = value,
+					("scope", ResolvedValue::String(value)) => scope = value,
+					_ => {}
+				}
+			}
+		}
+
+		subcommand.name
+	});
+
+	let content = match (subcommand, owner(command, scope)) {
+		(Some("show"), _) => show(config, storage, command)?,
+		(_, Err(why)) => why.to_string(),
+		(Some("set"), Ok(owner)) => set(config, storage, owner, patterns)?,
+		(Some("reset"), Ok(owner)) => {
+			if storage.lock().branch_preferences().remove(owner)? {
+				"Done! I'll show every branch I track again".to_string()
+			} else {
+				"Every branch I track is already being shown".to_string()
+			}
+		}
+		_ => "I don't know how to do that!".to_string(),
+	};
+
+	let resp = CreateInteractionResponseFollowup::new().content(content);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Figure out whose preference to change, making sure they're allowed to
+fn owner(command: &CommandInteraction, scope: &str) -> Result<Owner, &'static str> {
+	if scope != "server" {
+		return Ok(Owner::User(command.user.id));
+	}
+
+	let Some(guild_id) = command.guild_id else {
+		return Err("There's no server to change settings for here!");
+	};
+
+	let manages_guild = command
+		.member
+		.as_ref()
+		.and_then(|member| member.permissions)
+		.is_some_and(Permissions::manage_guild);
+	if !manages_guild {
+		return Err("Only people who can manage this server can change its settings");
+	}
+
+	Ok(Owner::Guild(guild_id))
+}
+
+/// Pick which branches to show an [`Owner`], making sure they match something first
+fn set(config: &Config, storage: &Storage, owner: Owner, input: &str) -> Result<String> {
+	let patterns: Vec<String> = input
+		.split([',', ' '])
+		.map(str::trim)
+		.filter(|pattern| !pattern.is_empty())
+		.map(ToString::to_string)
+		.collect();
+	if patterns.is_empty() {
+		return Ok("I need at least one branch to show!".to_string());
+	}
+
+	let tracked = config.nixpkgs_branches();
+	for pattern in &patterns {
+		let matches_any = Pattern::new(pattern)
+			.is_ok_and(|compiled| tracked.iter().any(|branch| compiled.matches(branch)));
+		if !matches_any {
+			return Ok(format!(
+				"`{pattern}` doesn't match any branch I track. I track: {}",
+				code_list(&tracked)
+			));
+		}
+	}
+
+	storage.lock().branch_preferences().set(owner, &patterns)?;
+	let shown = branches::matching(&patterns, tracked);
+	Ok(format!("Done! I'll show {}", code_list(&shown)))
+}
+
+/// Describe which branches are shown, and who picked them
+fn show(config: &Config, storage: &Storage, command: &CommandInteraction) -> Result<String> {
+	let handle = storage.lock();
+	let preferences = handle.branch_preferences();
+	let guild = command
+		.guild_id
+		.map(|guild_id| preferences.get(Owner::Guild(guild_id)))
+		.transpose()?
+		.flatten();
+	let user = preferences.get(Owner::User(command.user.id))?;
+
+	let describe = |patterns: Option<&Vec<String>>| {
+		patterns.map_or_else(
+			|| "every branch".to_string(),
+			|patterns| code_list(patterns),
+		)
+	};
+	let mut lines = vec![];
+	if command.guild_id.is_some() {
+		lines.push(format!("**This server**: {}", describe(guild.as_ref())));
+	}
+	lines.push(format!("**You**: {}", describe(user.as_ref())));
+
+	// a server's choice wins over a user's
+	let shown = match guild.or(user) {
+		Some(patterns) => branches::matching(&patterns, config.nixpkgs_branches()),
+		None => config.nixpkgs_branches(),
+	};
+	lines.push(format!("**Showing here**: {}", code_list(&shown)));
+
+	Ok(lines.join("\n"))
+}
+
+fn code_list(items: &[String]) -> String {
+	items
+		.iter()
+		.map(|item| format!("`{item}`"))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+fn scope_option() -> CreateCommandOption {
+	CreateCommandOption::new(
+		CommandOptionType::String,
+		"scope",
+		"Who this is for. Defaults to just you",
+	)
+	.add_string_choice("Just me", "me")
+	.add_string_choice("Everyone in this server", "server")
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("config")
+		.description("Change how I work for you or your server")
+		.add_integration_type(InstallationContext::Guild)
+		.add_integration_type(InstallationContext::User)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::SubCommandGroup,
+				"branches",
+				"Which branches to show",
+			)
+			.add_sub_option(
+				CreateCommandOption::new(
+					CommandOptionType::SubCommand,
+					"set",
+					"Only show some of the branches I track",
+				)
+				.add_sub_option(
+					CreateCommandOption::new(
+						CommandOptionType::String,
+						"branches",
+						"Branch names or patterns, separated by commas (i.e., `nixos-2?.??,master`)",
+					)
+					.required(true),
+				)
+				.add_sub_option(scope_option()),
+			)
+			.add_sub_option(
+				CreateCommandOption::new(
+					CommandOptionType::SubCommand,
+					"reset",
+					"Show every branch I track again",
+				)
+				.add_sub_option(scope_option()),
+			)
+			.add_sub_option(CreateCommandOption::new(
+				CommandOptionType::SubCommand,
+				"show",
+				"See which branches are shown",
+			)),
+		)
+}
@@ -1,8 +1,34 @@
-use serenity::builder::CreateCommand;
+use crate::{
+	config::Config,
+	http::{Base, GitHubClientExt, PullRequest},
+	storage::Storage,
+};
+
+use std::sync::Arc;
+
+use eyre::Result;
+use git_tracker::{Merge, MergedPullRequest, Oid};
+use log::{trace, warn};
+use serenity::builder::{CreateCommand, CreateInteractionResponseFollowup};
+use serenity::model::{
+	application::{CommandInteraction, ResolvedOption, ResolvedValue},
+	Timestamp,
+};
+use serenity::prelude::Context;
 
 pub mod about;
+pub mod channels;
+pub mod commit;
+pub mod config;
 pub mod ping;
+pub mod subscribe;
 pub mod track;
+pub mod track_commit;
+pub mod track_message;
+pub mod watch;
+
+pub(crate) const REPO_OWNER: &str = "NixOS";
+pub(crate) const REPO_NAME: &str = "nixpkgs";
 
 macro_rules! cmd {
 	($module: ident) => {
@@ -13,5 +39,155 @@ macro_rules! cmd {
 /// Return a list of all our [`CreateCommand`]s
 #[must_use]
 pub fn to_vec() -> Vec<CreateCommand> {
-	vec![cmd!(about), cmd!(ping), cmd!(track)]
+	vec![
+		cmd!(about),
+		cmd!(channels),
+		cmd!(commit),
+		cmd!(config),
+		cmd!(ping),
+		cmd!(subscribe),
+		cmd!(track),
+		cmd!(track_commit),
+		cmd!(track_message),
+		cmd!(watch),
+	]
+}
+
+/// A commit a user asked about
+pub struct Lookup {
+	/// Full SHA of the commit
+	pub sha: String,
+	/// Where the commit was first merged, if it's on any branch we track
+	pub merge: Option<Merge>,
+}
+
+/// Find the commit in a command's `sha` option, and where it was first merged
+///
+/// The command should already be deferred. If the commit can't be found, the user is told why
+/// and [`None`] is returned
+///
+/// # Errors
+///
+/// Will return [`Err`] if the repository can't be searched or we can't respond
+pub async fn look_up_commit(
+	ctx: &Context,
+	config: &Config,
+	command: &CommandInteraction,
+) -> Result<Option<Lookup>> {
+	let options = command.data.options();
+	let problem = match options.first() {
+		Some(ResolvedOption {
+			value: ResolvedValue::String(sha),
+			..
+		}) => match resolve_commit(config, sha).await? {
+			Ok(commit) => {
+				let sha = commit.to_string();
+				let merge = config
+					.repository()
+					.find_merge(config.nixpkgs_branches(), sha.clone())
+					.await?;
+
+				return Ok(Some(Lookup { sha, merge }));
+			}
+			Err(why) => why,
+		},
+		_ => "I need a commit SHA!".to_string(),
+	};
+
+	let resp = CreateInteractionResponseFollowup::new().content(problem);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(None)
+}
+
+/// Find the commit a (possibly abbreviated) SHA from a user refers to
+///
+/// If it can't be found, the inner [`Err`] explains why in a way we can show the user
+///
+/// # Errors
+///
+/// Will return [`Err`] if the repository can't be searched
+async fn resolve_commit(config: &Config, sha: &str) -> Result<Result<Oid, String>> {
+	let sha = sha.trim();
+	// git won't abbreviate anything shorter than this
+	if !(4..=40).contains(&sha.len()) || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+		return Ok(Err(format!(
+			"`{sha}` doesn't look like a commit SHA. It should be 4 to 40 hexadecimal characters"
+		)));
+	}
+
+	match config.repository().resolve_commit(sha.to_string()).await {
+		Ok(Some(commit)) => Ok(Ok(commit)),
+		Ok(None) => Ok(Err(format!(
+			"No commit I have starts with `{sha}`. It might not be merged yet"
+		))),
+		Err(git_tracker::Error::AmbiguousSha(_)) => Ok(Err(format!(
+			"`{sha}` is too short to tell which commit you mean, since more than one object \
+			 starts with it! Try a longer SHA"
+		))),
+		Err(why) => Err(why.into()),
+	}
+}
+
+/// Look up a nixpkgs PR, preferring our cache when we can
+///
+/// When GitHub can't be reached, merged PRs are looked up in the repository's PR index instead
+///
+/// # Errors
+///
+/// Will return [`Err`] if the PR can't be fetched from GitHub or the cache can't be used
+pub async fn pull_request<T>(
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	id: u64,
+) -> Result<PullRequest>
+where
+	T: GitHubClientExt,
+{
+	if let Some(pull_request) = storage.lock().pull_requests().get(id)? {
+		trace!("Using cached information for PR #{id}");
+		return Ok(pull_request);
+	}
+
+	let pull_request = match http.pull_request(REPO_OWNER, REPO_NAME, id).await {
+		Ok(pull_request) => pull_request,
+		Err(why) => {
+			let Some(merged) = config.repository().find_merged_pull_request(id).await? else {
+				return Err(why.into());
+			};
+
+			warn!("Couldn't get PR #{id} from GitHub, using our index instead\n{why:?}");
+			// this is missing some information, so don't cache it
+			return Ok(from_index(merged));
+		}
+	};
+	// only merged PRs are set in stone
+	if pull_request.merged {
+		storage.lock().pull_requests().insert(&pull_request)?;
+	}
+
+	Ok(pull_request)
+}
+
+/// Make do with what our PR index knows about a merged PR
+fn from_index(merged: MergedPullRequest) -> PullRequest {
+	PullRequest {
+		html_url: format!(
+			"https://github.com/{REPO_OWNER}/{REPO_NAME}/pull/{}",
+			merged.number
+		),
+		number: merged.number,
+		title: merged
+			.title
+			.unwrap_or_else(|| format!("PR #{}", merged.number)),
+		merged: true,
+		merged_at: Timestamp::from_unix_timestamp(merged.time.seconds())
+			.ok()
+			.map(|timestamp| timestamp.to_string()),
+		merge_commit_sha: Some(merged.commit.to_string()),
+		base: Base {
+			branch: merged.branch,
+		},
+	}
 }
@@ -0,0 +1,217 @@
+use crate::{
+	config::Config,
+	http::{GitHubClientExt, PullRequest},
+	storage::Storage,
+	subscriptions::{self, Subscription, Target},
+};
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use eyre::Result;
+use serenity::builder::{
+	CreateCommand, CreateCommandOption, CreateEmbed, CreateInteractionResponseFollowup,
+};
+use serenity::model::application::{
+	AuthorizingIntegrationOwner, CommandInteraction, CommandOptionType, InstallationContext,
+	ResolvedValue,
+};
+use serenity::prelude::Context;
+
+pub async fn respond<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	command.defer_ephemeral(&ctx).await?;
+
+	let options = command.data.options();
+	let mut pr = None;
+	let mut notify_channel = false;
+	for option in &options {
+		match (option.name, &option.value) {
+			("pull_request", ResolvedValue::Integer(value)) => pr = Some(*value),
+			("notify", ResolvedValue::String(value)) => notify_channel = *value == "channel",
+			_ => {}
+		}
+	}
+
+	let Some(Ok(id)) = pr.map(u64::try_from) else {
+		let resp = CreateInteractionResponseFollowup::new()
+			.content("PR numbers aren't negative or that big...");
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	};
+
+	// find out what commit our PR was merged in
+	let pull_request = super::pull_request(http, config, storage, id).await?;
+	if !pull_request.merged {
+		let response = CreateInteractionResponseFollowup::new()
+			.content("It looks like that PR isn't merged yet! Try again when it is 😄");
+		command.create_followup(&ctx, response).await?;
+
+		return Ok(());
+	}
+
+	let Some(commit_sha) = pull_request.merge_commit_sha.clone() else {
+		let response = CreateInteractionResponseFollowup::new()
+			.content("It seems this pull request is very old. I can't track it");
+		command.create_followup(&ctx, response).await?;
+
+		return Ok(());
+	};
+
+	// we can only post in channels of guilds we're actually installed in
+	let installed_in_guild = command
+		.authorizing_integration_owners
+		.0
+		.iter()
+		.any(|owner| matches!(owner, AuthorizingIntegrationOwner::GuildInstall(Some(_))));
+	let target = if notify_channel && installed_in_guild {
+		Target::Channel(command.channel_id)
+	} else {
+		Target::User(command.user.id)
+	};
+
+	let waiting_for = match subscribe(config, storage, &pull_request, commit_sha, target).await? {
+		Subscribed::Waiting(branches) => branches,
+		outcome => {
+			let response =
+				CreateInteractionResponseFollowup::new().content(outcome.describe(&pull_request));
+			command.create_followup(&ctx, response).await?;
+
+			return Ok(());
+		}
+	};
+
+	let destination = match target {
+		Target::User(_) if notify_channel => {
+			"in your DMs, since I'm not installed in this server".to_string()
+		}
+		Target::User(_) => "in your DMs".to_string(),
+		Target::Channel(channel_id) => format!("in <#{channel_id}>"),
+	};
+	let embed = CreateEmbed::new()
+		.title(format!("Subscribed to Nixpkgs PR #{}", pull_request.number))
+		.url(&pull_request.html_url)
+		.description(format!(
+			"I'll let you know {destination} as it reaches: {}",
+			waiting_for.join(", ")
+		));
+	let resp = CreateInteractionResponseFollowup::new().embed(embed);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// What came of [`subscribe`]ing to a PR
+pub enum Subscribed {
+	/// The PR is still headed for these branches
+	Waiting(Vec<String>),
+	/// The PR has already reached every branch we track that it will
+	Reached,
+	/// The PR won't reach any branch we track
+	Untracked,
+}
+
+impl Subscribed {
+	/// Explain why there's nothing to wait for
+	pub fn describe(&self, pull_request: &PullRequest) -> String {
+		match self {
+			Self::Waiting(branches) => format!(
+				"I'll let you know as PR #{} reaches: {}",
+				pull_request.number,
+				branches.join(", ")
+			),
+			Self::Reached => {
+				"This PR has already reached every branch I track! Nothing to wait for 🎉"
+					.to_string()
+			}
+			Self::Untracked => format!(
+				"PRs merged into `{}` don't make it to any branch I track, so I won't have \
+				anything to tell you",
+				pull_request.base.branch
+			),
+		}
+	}
+}
+
+/// Subscribe a [`Target`] to a merged PR
+///
+/// Nothing is saved unless the PR is still headed for a branch we track
+///
+/// # Errors
+///
+/// Will return [`Err`] if the repository can't be checked or the subscription can't be saved
+pub async fn subscribe(
+	config: &Config,
+	storage: &Storage,
+	pull_request: &PullRequest,
+	commit_sha: String,
+	target: Target,
+) -> Result<Subscribed> {
+	let branches = subscriptions::relevant_branches(config, Some(&pull_request.base.branch));
+	if branches.is_empty() {
+		return Ok(Subscribed::Untracked);
+	}
+
+	// don't notify about anywhere the PR has already been
+	let reached_branches: HashSet<_> = config
+		.repository()
+		.branches_contain_sha(branches.clone(), commit_sha.clone())
+		.await?
+		.into_iter()
+		.filter(|(_, has_commit)| *has_commit)
+		.map(|(name, _)| name)
+		.collect();
+
+	let waiting_for: Vec<_> = branches
+		.into_iter()
+		.filter(|branch| !reached_branches.contains(branch))
+		.collect();
+	if waiting_for.is_empty() {
+		return Ok(Subscribed::Reached);
+	}
+
+	storage.lock().subscriptions().insert(&Subscription {
+		pull_request: pull_request.number,
+		title: pull_request.title.clone(),
+		html_url: pull_request.html_url.clone(),
+		commit_sha,
+		target,
+		base_ref: Some(pull_request.base.branch.clone()),
+		reached_branches,
+	})?;
+
+	Ok(Subscribed::Waiting(waiting_for))
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("subscribe")
+		.description("Get notified as a nixpkgs PR reaches each branch")
+		.add_integration_type(InstallationContext::Guild)
+		.add_integration_type(InstallationContext::User)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::Integer,
+				"pull_request",
+				"PR to subscribe to",
+			)
+			.required(true),
+		)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::String,
+				"notify",
+				"Where to send notifications",
+			)
+			.add_string_choice("Direct message", "dm")
+			.add_string_choice("This channel", "channel"),
+		)
+}
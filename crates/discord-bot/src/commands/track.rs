@@ -1,29 +1,420 @@
-use crate::{config::Config, http::GitHubClientExt};
+use super::subscribe::Subscribed;
+use crate::{
+	branches,
+	config::Config,
+	http::{GitHubClientExt, PullRequest},
+	pipeline::{self, Pipeline, Pipelines},
+	preferences, references,
+	storage::Storage,
+	subscriptions::Target,
+};
 
+use std::collections::HashMap;
+use std::iter;
 use std::sync::Arc;
 use std::time::Instant;
 
 use eyre::Result;
-use log::debug;
+use git_tracker::{Backport, Landing, Merge, Tag};
+use log::{debug, trace, warn};
 use serenity::builder::{
-	CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
-	CreateInteractionResponseFollowup,
+	CreateActionRow, CreateAutocompleteResponse, CreateButton, CreateCommand, CreateCommandOption,
+	CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseFollowup,
+	EditInteractionResponse,
 };
 use serenity::model::{
 	application::{
-		CommandInteraction, CommandOptionType, InstallationContext, ResolvedOption, ResolvedValue,
+		ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction,
+		InstallationContext, ResolvedValue,
 	},
 	Timestamp,
 };
 use serenity::prelude::Context;
 
-const REPO_OWNER: &str = "NixOS";
-const REPO_NAME: &str = "nixpkgs";
+/// The most suggestions Discord will show for an option
+const MAX_CHOICES: usize = 25;
+
+/// What the custom IDs of our [`buttons`] start with
+pub const COMPONENT_PREFIX: &str = "track";
+
+/// How a PR made it into a branch
+#[derive(Clone, Copy, Debug)]
+enum Reached {
+	/// The PR's merge commit is in the branch
+	Merged(Landing),
+	/// A copy of the PR's changes is in the branch
+	Backported(Backport),
+}
+
+/// Where a commit has made it
+pub struct Status {
+	/// Branches we checked, in the order we show them
+	branches: Vec<String>,
+	/// How changes move through `branches`
+	pipelines: Pipelines,
+	/// How the commit reached each branch we checked, if it did
+	landings: HashMap<String, Option<Reached>>,
+	/// When we saw channels we checked pick up the commit, as Unix timestamps
+	arrivals: HashMap<String, i64>,
+	/// Whether we checked for releases containing the commit
+	checked_tags: bool,
+	/// The first release containing the commit
+	first_tag: Option<Tag>,
+}
+
+impl Status {
+	/// Check where a commit has landed on `branches`, and which release first included it
+	///
+	/// `merge` is where the commit was first merged, which already knows where else it landed
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository or our records of branches can't be checked
+	pub async fn check(
+		config: &Config,
+		storage: &Storage,
+		branches: Vec<String>,
+		commit_sha: &str,
+		merge: Option<&Merge>,
+	) -> Result<Self> {
+		let landings = branches
+			.iter()
+			.map(|branch| {
+				let landing = merge.and_then(|merge| merge.landings.get(branch));
+				(branch.clone(), landing.copied().map(Reached::Merged))
+			})
+			.collect();
+		let mut status = Self::with_landings(config, branches, landings, commit_sha).await?;
+		status.find_arrivals(config, storage, commit_sha).await?;
+
+		Ok(status)
+	}
+
+	/// Check where a commit has landed on `branches`, and which release first included it,
+	/// without looking for when channels got it
+	async fn check_landings(
+		config: &Config,
+		branches: Vec<String>,
+		commit_sha: &str,
+	) -> Result<Self> {
+		let landings = config
+			.repository()
+			.branches_landing_of_sha(branches.clone(), commit_sha.to_string())
+			.await?
+			.into_iter()
+			.map(|(name, landing)| (name, landing.map(Reached::Merged)))
+			.collect();
+
+		Self::with_landings(config, branches, landings, commit_sha).await
+	}
+
+	/// Find which release first included a commit that reached `landings`
+	async fn with_landings(
+		config: &Config,
+		branches: Vec<String>,
+		landings: HashMap<String, Option<Reached>>,
+		commit_sha: &str,
+	) -> Result<Self> {
+		let checked_tags = config.fetch_tags();
+		let first_tag = if checked_tags {
+			config
+				.repository()
+				.first_tag_containing(commit_sha.to_string())
+				.await?
+		} else {
+			None
+		};
+
+		Ok(Self {
+			branches,
+			pipelines: config.pipelines().clone(),
+			landings,
+			arrivals: HashMap::new(),
+			checked_tags,
+			first_tag,
+		})
+	}
+
+	/// Check where a merged PR has made it, including any backports of it
+	///
+	/// Only branches matching `preferred` are checked, if it's set
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository or our cache can't be checked
+	pub async fn of_pull_request<T>(
+		http: &Arc<T>,
+		config: &Config,
+		storage: &Storage,
+		pull_request: &PullRequest,
+		commit_sha: &str,
+		preferred: Option<&[String]>,
+	) -> Result<Self>
+	where
+		T: GitHubClientExt,
+	{
+		let only_preferred = |branches| match preferred {
+			Some(patterns) => branches::matching(patterns, branches),
+			None => branches,
+		};
+
+		// the merge commit will never reach branches outside of its base's pipeline...
+		let branches = only_preferred(config.downstream_branches(&pull_request.base.branch));
+		let mut status = Self::check_landings(config, branches, commit_sha).await?;
+		// ...but the PR can be backported to them
+		let candidates = only_preferred(config.backport_branches(&pull_request.base.branch));
+		status
+			.find_backports(
+				http,
+				config,
+				storage,
+				pull_request.number,
+				commit_sha,
+				candidates,
+			)
+			.await?;
+		status.find_arrivals(config, storage, commit_sha).await?;
+
+		Ok(status)
+	}
+
+	/// Check where a merged PR has made it on `branches`, including any backports of it
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository or our cache can't be checked
+	pub async fn of_pull_request_on<T>(
+		http: &Arc<T>,
+		config: &Config,
+		storage: &Storage,
+		number: u64,
+		commit_sha: &str,
+		branches: Vec<String>,
+	) -> Result<Self>
+	where
+		T: GitHubClientExt,
+	{
+		let mut status = Self::check_landings(config, branches, commit_sha).await?;
+		status
+			.find_backports(http, config, storage, number, commit_sha, vec![])
+			.await?;
+		status.find_arrivals(config, storage, commit_sha).await?;
+
+		Ok(status)
+	}
+
+	/// Look for backports of a PR on every branch its merge commit hasn't reached, and on
+	/// `candidates` we haven't checked
+	///
+	/// When a backport is found on a candidate, it's shown along with the rest of its pipeline
+	/// that's among `candidates`, and whether each of those has picked it up yet
+	async fn find_backports<T>(
+		&mut self,
+		http: &Arc<T>,
+		config: &Config,
+		storage: &Storage,
+		number: u64,
+		commit_sha: &str,
+		candidates: Vec<String>,
+	) -> Result<()>
+	where
+		T: GitHubClientExt,
+	{
+		// backports are only ever merged into `release-*` branches. everything after them
+		// picks them up from there
+		let mut search: Vec<_> = self
+			.landings
+			.iter()
+			.filter(|(name, reached)| reached.is_none() && pipeline::takes_backports(name))
+			.map(|(name, _)| name.clone())
+			.collect();
+		search.extend(
+			candidates
+				.iter()
+				.filter(|candidate| pipeline::takes_backports(candidate))
+				.filter(|candidate| !self.landings.contains_key(*candidate))
+				.cloned(),
+		);
+		if search.is_empty() {
+			return Ok(());
+		}
+
+		let commit_shas = pull_request_commits(http, storage, number).await?;
+		let repository = config.repository();
+		let backports = repository
+			.branches_backports_of_shas(search, commit_sha.to_string(), commit_shas, Some(number))
+			.await?;
+		for (name, backport) in backports {
+			let Some(backport) = backport else {
+				continue;
+			};
+
+			// the rest of its pipeline we're showing (or would, with a backport)
+			let rest: Vec<_> = config
+				.downstream_branches(&name)
+				.into_iter()
+				.filter(|branch| *branch != name)
+				.filter(|branch| match self.landings.get(branch) {
+					Some(reached) => reached.is_none(),
+					None => candidates.contains(branch),
+				})
+				.collect();
+			if !self.landings.contains_key(&name) {
+				self.branches.push(name.clone());
+			}
+			self.landings
+				.insert(name, Some(Reached::Backported(backport)));
+
+			let landings = repository
+				.branches_landing_of_sha(rest, backport.commit.to_string())
+				.await?;
+			for (branch, landing) in landings {
+				if !self.landings.contains_key(&branch) {
+					self.branches.push(branch.clone());
+				}
+				let reached = landing.map(|landing| {
+					Reached::Backported(Backport {
+						landing: Some(landing),
+						..backport
+					})
+				});
+				self.landings.insert(branch, reached);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Find when we saw each channel we checked pick up the commit (or its backport)
+	///
+	/// Channels share the history of the branch they follow, so their landings only tell when
+	/// a change was merged upstream. Our own record of their advances is used instead, as long
+	/// as it goes back far enough
+	async fn find_arrivals(
+		&mut self,
+		config: &Config,
+		storage: &Storage,
+		commit_sha: &str,
+	) -> Result<()> {
+		for (branch, reached) in &self.landings {
+			let commit = match reached {
+				_ if !pipeline::is_channel(branch) => continue,
+				Some(Reached::Merged(_)) => commit_sha.to_string(),
+				Some(Reached::Backported(backport)) => backport.commit.to_string(),
+				None => continue,
+			};
+
+			let history = storage.lock().branch_advances().history(branch)?;
+			let Some(first) = history.first() else {
+				continue;
+			};
+			// every tip we've seen the channel at, starting from before its first advance
+			let tips = iter::once(first.old_sha.clone())
+				.chain(history.iter().map(|advance| advance.new_sha.clone()))
+				.collect();
+
+			// if it already had the commit when we started watching, we don't know when it got it
+			if let Some(index @ 1..) = config.repository().first_containing(tips, commit).await? {
+				self.arrivals
+					.insert(branch.clone(), history[index - 1].seen_at);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Describe whether the commit has reached a branch, how, and when
+	fn branch_status(&self, branch: &str) -> String {
+		landing_status(
+			branch,
+			self.landings.get(branch).copied().flatten().as_ref(),
+			self.arrivals.get(branch).copied(),
+		)
+	}
+
+	/// Get the branches we checked that aren't part of any of `pipelines`
+	fn loose_branches<'a>(&'a self, pipelines: &'a [Pipeline]) -> impl Iterator<Item = &'a String> {
+		self.branches
+			.iter()
+			.filter(|branch| !pipelines.iter().any(|pipeline| pipeline.contains(branch)))
+	}
+
+	/// Describe the first release containing the commit, if we checked
+	fn release_status(&self) -> Option<String> {
+		self.checked_tags.then(|| {
+			self.first_tag.as_ref().map_or_else(
+				|| "Not in a release yet".to_string(),
+				|tag| format!("`{}` <t:{}:R>", tag.name, tag.time.seconds()),
+			)
+		})
+	}
+
+	/// Whether the commit has reached every branch we checked
+	pub fn reached_everywhere(&self) -> bool {
+		self.landings.values().all(Option::is_some)
+	}
+
+	/// Describe where the commit has made it as embed fields, one per pipeline
+	pub fn fields(&self) -> Vec<(String, String, bool)> {
+		let pipelines = self.pipelines.for_branches(&self.branches);
+		let mut fields: Vec<_> = pipelines
+			.iter()
+			.map(|pipeline| pipeline_field(pipeline, self))
+			.collect();
+		// anything that isn't part of a pipeline is shown on its own
+		fields.extend(
+			self.loose_branches(&pipelines)
+				.map(|branch| (branch.clone(), self.branch_status(branch), true)),
+		);
+
+		if let Some(status) = self.release_status() {
+			fields.push(("First released in".to_string(), status, false));
+		}
+
+		fields
+	}
+
+	/// Describe where the commit has made it in a few lines, for when there isn't much room
+	pub fn summary(&self) -> String {
+		let reached = |branch: &str| self.landings.get(branch).is_some_and(Option::is_some);
+		let pipelines = self.pipelines.for_branches(&self.branches);
+
+		let mut lines: Vec<_> = pipelines
+			.iter()
+			.map(|pipeline| {
+				let progress = pipeline.progress(reached);
+				let furthest = if progress.furthest.is_empty() {
+					"nothing yet".to_string()
+				} else {
+					listed("✅", &progress.furthest)
+				};
+				let next = if progress.next.is_empty() {
+					"🎉".to_string()
+				} else {
+					listed("⏳", &progress.next)
+				};
+
+				format!("**{}**: {furthest} → {next}", pipeline.name)
+			})
+			.collect();
+		lines.extend(
+			self.loose_branches(&pipelines)
+				.map(|branch| format!("**{branch}**: {}", self.branch_status(branch))),
+		);
+
+		if let Some(status) = self.release_status() {
+			lines.push(format!("**First released in**: {status}"));
+		}
+
+		lines.join("\n")
+	}
+}
 
 pub async fn respond<T>(
 	ctx: &Context,
 	http: &Arc<T>,
 	config: &Config,
+	storage: &Storage,
 	command: &CommandInteraction,
 ) -> Result<()>
 where
@@ -33,64 +424,235 @@ where
 	command.defer(&ctx).await?;
 
 	let options = command.data.options();
-	let Some(ResolvedOption {
-		value: ResolvedValue::Integer(pr),
-		..
-	}) = options.first()
-	else {
-		let resp = CreateInteractionResponseFollowup::new()
-			.content("PR numbers aren't negative or that big...");
-		command.create_followup(&ctx, resp).await?;
+	let mut input = "";
+	let mut branch = None;
+	for option in &options {
+		match (option.name, &option.value) {
+			("pull_request", ResolvedValue::String(value)) => input = value,
+			("branch", ResolvedValue::String(value)) => branch = Some(*value),
+			_ => {}
+		}
+	}
 
-		return Ok(());
+	let id = match references::parse_pull_request(input) {
+		Ok(id) => id,
+		Err(why) => {
+			let resp = CreateInteractionResponseFollowup::new().content(why.to_string());
+			command.create_followup(&ctx, resp).await?;
+
+			return Ok(());
+		}
 	};
 
-	let Ok(id) = u64::try_from(*pr) else {
-		let resp = CreateInteractionResponseFollowup::new()
-			.content("PR numbers aren't negative or that big...");
-		command.create_followup(&ctx, resp).await?;
+	if let Some(branch) = branch {
+		if !config
+			.nixpkgs_branches()
+			.iter()
+			.any(|tracked| tracked == branch)
+		{
+			let resp = CreateInteractionResponseFollowup::new().content(format!(
+				"I don't track `{branch}`! Try one of the branches I suggest"
+			));
+			command.create_followup(&ctx, resp).await?;
 
-		return Ok(());
-	};
+			return Ok(());
+		}
+	}
 
 	// find out what commit our PR was merged in
-	let pull_request = http.pull_request(REPO_OWNER, REPO_NAME, id).await?;
+	let pull_request = super::pull_request(http, config, storage, id).await?;
+	match branch {
+		Some(branch) => {
+			respond_with_branch_status(ctx, http, config, storage, command, pull_request, branch)
+				.await
+		}
+		None => respond_with_status(ctx, http, config, storage, command, pull_request, None).await,
+	}
+}
+
+/// Suggest tracked branches matching what's been typed so far
+///
+/// # Errors
+///
+/// Will return [`Err`] if we can't respond
+pub async fn autocomplete(
+	ctx: &Context,
+	config: &Config,
+	interaction: &CommandInteraction,
+) -> Result<()> {
+	let typed = interaction
+		.data
+		.autocomplete()
+		.map(|option| option.value.to_lowercase())
+		.unwrap_or_default();
+
+	let response = config
+		.nixpkgs_branches()
+		.into_iter()
+		.filter(|branch| branch.contains(&typed))
+		.take(MAX_CHOICES)
+		.fold(CreateAutocompleteResponse::new(), |response, branch| {
+			response.add_string_choice(branch.clone(), branch)
+		});
+	interaction
+		.create_response(&ctx, CreateInteractionResponse::Autocomplete(response))
+		.await?;
+
+	Ok(())
+}
+
+/// Get the commit a PR was merged in, or explain to the user why we can't track it
+async fn merge_commit(
+	ctx: &Context,
+	command: &CommandInteraction,
+	pull_request: &PullRequest,
+) -> Result<Option<String>> {
 	if !pull_request.merged {
 		let response = CreateInteractionResponseFollowup::new()
 			.content("It looks like that PR isn't merged yet! Try again when it is 😄");
 		command.create_followup(&ctx, response).await?;
 
-		return Ok(());
+		return Ok(None);
 	}
 
 	// seems older PRs may not have this
-	let Some(commit_sha) = pull_request.merge_commit_sha else {
+	let Some(commit_sha) = pull_request.merge_commit_sha.clone() else {
 		let response = CreateInteractionResponseFollowup::new()
 			.content("It seems this pull request is very old. I can't track it");
 		command.create_followup(&ctx, response).await?;
 
+		return Ok(None);
+	};
+
+	Ok(Some(commit_sha))
+}
+
+/// Follow up on a deferred [`CommandInteraction`] with whether a PR has reached `branch`
+async fn respond_with_branch_status<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+	pull_request: PullRequest,
+	branch: &str,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	let Some(commit_sha) = merge_commit(ctx, command, &pull_request).await? else {
 		return Ok(());
 	};
 
-	let repository = config.repository();
-	let timer = Instant::now();
-	let branch_results = repository.branches_contain_sha(config.nixpkgs_branches(), &commit_sha)?;
-	let branch_check_time = timer.elapsed();
-	let fields: Vec<_> = branch_results
-		.iter()
-		.map(|(name, has_commit)| {
-			let emoji = if *has_commit { "✅" } else { "❌" };
-			(*name, emoji, true)
-		})
-		.collect();
+	let Some(embed) = status_embed(
+		http,
+		config,
+		storage,
+		&pull_request,
+		&commit_sha,
+		None,
+		Some(branch),
+	)
+	.await?
+	else {
+		return Ok(());
+	};
 
-	// if we didn't find any, bail
-	if fields.is_empty() {
+	let resp = CreateInteractionResponseFollowup::new()
+		.embed(embed)
+		.components(vec![buttons(pull_request.number, Some(branch))]);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Follow up on a deferred [`CommandInteraction`] with where a PR has reached
+///
+/// `context` is sent along with the status, to explain how we got there
+pub async fn respond_with_status<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+	pull_request: PullRequest,
+	context: Option<String>,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	let Some(commit_sha) = merge_commit(ctx, command, &pull_request).await? else {
+		return Ok(());
+	};
+
+	let preferred = preferences::lookup(storage, command.guild_id, command.user.id)?;
+	let Some(embed) = status_embed(
+		http,
+		config,
+		storage,
+		&pull_request,
+		&commit_sha,
+		preferred.as_deref(),
+		None,
+	)
+	.await?
+	else {
+		// if we didn't find any, bail
 		let response = CreateInteractionResponseFollowup::new()
 			.content("This PR has been merged...but I can't seem to find it anywhere. I might not be tracking it's base branch");
 		command.create_followup(&ctx, response).await?;
 
 		return Ok(());
+	};
+
+	let mut resp = CreateInteractionResponseFollowup::new()
+		.embed(embed)
+		.components(vec![buttons(pull_request.number, None)]);
+	if let Some(context) = context {
+		resp = resp.content(context);
+	}
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Build an embed showing where a merged PR has made it
+///
+/// When `branch` is set, only it is shown. Otherwise, the branches matching `preferred` are
+/// shown, and [`None`] is returned if it isn't on any of them
+async fn status_embed<T>(
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	pull_request: &PullRequest,
+	commit_sha: &str,
+	preferred: Option<&[String]>,
+	branch: Option<&str>,
+) -> Result<Option<CreateEmbed>>
+where
+	T: GitHubClientExt,
+{
+	let timer = Instant::now();
+	let fields = if let Some(branch) = branch {
+		let status = Status::of_pull_request_on(
+			http,
+			config,
+			storage,
+			pull_request.number,
+			commit_sha,
+			vec![branch.to_string()],
+		)
+		.await?;
+		vec![(branch.to_string(), status.branch_status(branch), false)]
+	} else {
+		Status::of_pull_request(http, config, storage, pull_request, commit_sha, preferred)
+			.await?
+			.fields()
+	};
+	let branch_check_time = timer.elapsed();
+
+	if fields.is_empty() {
+		return Ok(None);
 	}
 
 	let mut embed = CreateEmbed::new()
@@ -103,8 +665,8 @@ where
 			branch_check_time.as_millis()
 		)));
 
-	if let Some(merged_at) = pull_request.merged_at {
-		if let Ok(timestamp) = Timestamp::parse(&merged_at) {
+	if let Some(merged_at) = &pull_request.merged_at {
+		if let Ok(timestamp) = Timestamp::parse(merged_at) {
 			embed = embed.timestamp(timestamp);
 		} else {
 			debug!("Couldn't parse timestamp from GitHub! Ignoring.");
@@ -113,18 +675,301 @@ where
 		debug!("Couldn't find `merged_at` information for a supposedly merged PR! Ignoring.");
 	}
 
-	let resp = CreateInteractionResponseFollowup::new().embed(embed);
-	command.create_followup(&ctx, resp).await?;
+	Ok(Some(embed))
+}
+
+/// Buttons to check on a PR again, or get notified as it moves along
+///
+/// Their custom IDs look like `track:<action>:<number>[:<branch>]`
+fn buttons(number: u64, branch: Option<&str>) -> CreateActionRow {
+	let branch = branch
+		.map(|branch| format!(":{branch}"))
+		.unwrap_or_default();
+	CreateActionRow::Buttons(vec![
+		CreateButton::new(format!("{COMPONENT_PREFIX}:refresh:{number}{branch}"))
+			.label("Refresh")
+			.emoji('🔄')
+			.style(ButtonStyle::Secondary),
+		CreateButton::new(format!("{COMPONENT_PREFIX}:notify:{number}"))
+			.label("Notify me")
+			.emoji('🔔')
+			.style(ButtonStyle::Primary),
+	])
+}
+
+/// Handle a click on one of the [`buttons`] under a PR's status
+///
+/// # Errors
+///
+/// Will return [`Err`] if the PR can't be checked or we can't respond
+pub async fn handle_component<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	component: &ComponentInteraction,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	let mut parts = component.data.custom_id.splitn(4, ':').skip(1);
+	let action = parts.next();
+	let number = parts.next().and_then(|number| number.parse().ok());
+	let branch = parts.next();
+	let (Some(action), Some(number)) = (action, number) else {
+		warn!(
+			"Got a button we don't understand: {}",
+			component.data.custom_id
+		);
+		return Ok(());
+	};
+
+	match action {
+		"refresh" => refresh(ctx, http, config, storage, component, number, branch).await,
+		"notify" => notify(ctx, http, config, storage, component, number).await,
+		_ => {
+			warn!(
+				"Got a button we don't understand: {}",
+				component.data.custom_id
+			);
+			Ok(())
+		}
+	}
+}
+
+/// Check a PR again, and update the status it was clicked on
+async fn refresh<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	component: &ComponentInteraction,
+	number: u64,
+	branch: Option<&str>,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	// this will probably take a while
+	component.defer(&ctx).await?;
+
+	// only merged PRs get buttons
+	let pull_request = super::pull_request(http, config, storage, number).await?;
+	let Some(commit_sha) = &pull_request.merge_commit_sha else {
+		return Ok(());
+	};
+
+	// the branches shown are now whichever the person clicking would see
+	let preferred = preferences::lookup(storage, component.guild_id, component.user.id)?;
+	let Some(embed) = status_embed(
+		http,
+		config,
+		storage,
+		&pull_request,
+		commit_sha,
+		preferred.as_deref(),
+		branch,
+	)
+	.await?
+	else {
+		return Ok(());
+	};
+
+	component
+		.edit_response(&ctx, EditInteractionResponse::new().embed(embed))
+		.await?;
 
 	Ok(())
 }
 
+/// Subscribe whoever clicked to a PR, in their DMs
+async fn notify<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	component: &ComponentInteraction,
+	number: u64,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	component.defer_ephemeral(&ctx).await?;
+
+	let pull_request = super::pull_request(http, config, storage, number).await?;
+	let Some(commit_sha) = pull_request.merge_commit_sha.clone() else {
+		return Ok(());
+	};
+
+	let subscribed = super::subscribe::subscribe(
+		config,
+		storage,
+		&pull_request,
+		commit_sha,
+		Target::User(component.user.id),
+	)
+	.await?;
+	let content = match subscribed {
+		Subscribed::Waiting(waiting_for) => format!(
+			"I'll let you know in your DMs as PR #{number} reaches: {}",
+			waiting_for.join(", ")
+		),
+		outcome => outcome.describe(&pull_request),
+	};
+
+	let resp = CreateInteractionResponseFollowup::new()
+		.ephemeral(true)
+		.content(content);
+	component.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Get the SHAs of a PR's commits
+///
+/// These are saved for merged PRs we've cached, so we only ask GitHub once
+async fn pull_request_commits<T>(
+	http: &Arc<T>,
+	storage: &Storage,
+	number: u64,
+) -> Result<Vec<String>>
+where
+	T: GitHubClientExt,
+{
+	let cached = storage.lock().pull_requests().commits(number)?;
+	if let Some(commit_shas) = cached {
+		trace!("Using cached commits for PR #{number}");
+		return Ok(commit_shas);
+	}
+
+	match http
+		.pull_request_commits(super::REPO_OWNER, super::REPO_NAME, number)
+		.await
+	{
+		Ok(commits) => {
+			let commit_shas: Vec<_> = commits.into_iter().map(|commit| commit.sha).collect();
+			storage
+				.lock()
+				.pull_requests()
+				.insert_commits(number, &commit_shas)?;
+
+			Ok(commit_shas)
+		}
+		Err(why) => {
+			warn!("Couldn't get commits of PR #{number}!\n{why:?}");
+			Ok(vec![])
+		}
+	}
+}
+
+/// Describe whether a branch has been reached, how, and when
+///
+/// `arrival` is when we saw the branch get the change. Without it, channels can only be
+/// described by when the change was merged into the branch they follow
+fn landing_status(branch: &str, reached: Option<&Reached>, arrival: Option<i64>) -> String {
+	let when = |landing: Option<&Landing>| match (arrival, landing) {
+		(Some(arrival), _) => Some(format!("<t:{arrival}:R>")),
+		(None, Some(landing)) if pipeline::is_channel(branch) => {
+			Some(format!("merged upstream <t:{}:R>", landing.time.seconds()))
+		}
+		(None, Some(landing)) => Some(format!("<t:{}:R>", landing.time.seconds())),
+		(None, None) => None,
+	};
+
+	match reached {
+		None => "❌".to_string(),
+		Some(Reached::Merged(landing)) => {
+			format!("✅ {}", when(Some(landing)).unwrap_or_default())
+		}
+		Some(Reached::Backported(backport)) => {
+			let number = backport
+				.pull_request
+				.map(|number| format!(" #{number}"))
+				.unwrap_or_default();
+			let time = when(backport.landing.as_ref())
+				.map(|time| format!(" {time}"))
+				.unwrap_or_default();
+
+			format!("✅ via backport{number}{time}")
+		}
+	}
+}
+
+/// List branches with the same mark in front of each
+fn listed(mark: &str, branches: &[&str]) -> String {
+	branches
+		.iter()
+		.map(|branch| format!("{mark} `{branch}`"))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Describe how far a PR has made it through a [`Pipeline`]
+fn pipeline_field(pipeline: &Pipeline, status: &Status) -> (String, String, bool) {
+	let landing = |branch: &str| status.landings.get(branch).copied().flatten();
+	let progress = pipeline.progress(|branch| landing(branch).is_some());
+
+	let furthest = if progress.furthest.is_empty() {
+		"Reached: nothing yet".to_string()
+	} else {
+		progress
+			.furthest
+			.iter()
+			.map(|branch| format!("Reached: `{branch}` {}", status.branch_status(branch)))
+			.collect::<Vec<_>>()
+			.join("\n")
+	};
+	let next = if progress.next.is_empty() {
+		"Next: nothing, it's made it all the way 🎉".to_string()
+	} else {
+		format!("Next: {}", listed("⏳", &progress.next))
+	};
+	// parallel stages get a line of their own
+	let stages = pipeline
+		.chains()
+		.iter()
+		.map(|chain| {
+			chain
+				.iter()
+				.map(|stage| {
+					let mark = if landing(stage).is_some() {
+						"✅"
+					} else {
+						"❌"
+					};
+					format!("{mark} `{stage}`")
+				})
+				.collect::<Vec<_>>()
+				.join(" → ")
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	(
+		format!("{} pipeline", pipeline.name),
+		format!("{furthest}\n{next}\n{stages}"),
+		false,
+	)
+}
+
 pub fn register() -> CreateCommand {
 	CreateCommand::new("track")
 		.description("Track a nixpkgs PR")
 		.add_integration_type(InstallationContext::User)
 		.add_option(
-			CreateCommandOption::new(CommandOptionType::Integer, "pull_request", "PR to track")
-				.required(true),
+			CreateCommandOption::new(
+				CommandOptionType::String,
+				"pull_request",
+				"PR to track, as a number, #123, NixOS/nixpkgs#123, or a link",
+			)
+			.required(true),
+		)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::String,
+				"branch",
+				"Only check this branch",
+			)
+			.set_autocomplete(true),
 		)
 }
@@ -0,0 +1,80 @@
+use super::track::Status;
+use crate::{branches, config::Config, preferences, storage::Storage};
+
+use std::time::Instant;
+
+use eyre::Result;
+use serenity::builder::{
+	CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
+	CreateInteractionResponseFollowup,
+};
+use serenity::model::application::{CommandInteraction, CommandOptionType, InstallationContext};
+use serenity::prelude::Context;
+
+pub async fn respond(
+	ctx: &Context,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()> {
+	// this will probably take a while
+	command.defer(&ctx).await?;
+
+	let timer = Instant::now();
+	let Some(super::Lookup { sha, merge }) = super::look_up_commit(ctx, config, command).await?
+	else {
+		return Ok(());
+	};
+
+	// commits don't have a base branch, so we use wherever they were first merged
+	let mut branches = merge.as_ref().map_or_else(
+		|| config.nixpkgs_branches(),
+		|merge| config.downstream_branches(&merge.branch),
+	);
+	if let Some(patterns) = preferences::lookup(storage, command.guild_id, command.user.id)? {
+		branches = branches::matching(&patterns, branches);
+	}
+	let status = Status::check(config, storage, branches, &sha, merge.as_ref()).await?;
+	let branch_check_time = timer.elapsed();
+
+	let description = match &merge {
+		Some(merge) => match merge.pull_request {
+			Some(number) => format!("Merged into `{}` by PR #{number}", merge.branch),
+			None => format!("Pushed directly to `{}`", merge.branch),
+		},
+		None => "This commit isn't on any branch I track yet".to_string(),
+	};
+
+	let embed = CreateEmbed::new()
+		.title(format!("Nixpkgs Commit {} Status", &sha[..12]))
+		.url(format!(
+			"https://github.com/{}/{}/commit/{sha}",
+			super::REPO_OWNER,
+			super::REPO_NAME
+		))
+		.description(description)
+		.fields(status.fields())
+		.footer(CreateEmbedFooter::new(format!(
+			"Completed in {}ms",
+			branch_check_time.as_millis()
+		)));
+
+	let resp = CreateInteractionResponseFollowup::new().embed(embed);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("track-commit")
+		.description("Track a nixpkgs commit")
+		.add_integration_type(InstallationContext::User)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::String,
+				"sha",
+				"Commit to track. Short SHAs work, too",
+			)
+			.required(true),
+		)
+}
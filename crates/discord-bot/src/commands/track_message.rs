@@ -0,0 +1,120 @@
+use super::track::Status;
+use crate::{config::Config, http::GitHubClientExt, preferences, references, storage::Storage};
+
+use std::sync::Arc;
+
+use eyre::Result;
+use log::warn;
+use serenity::builder::{CreateCommand, CreateEmbed, CreateInteractionResponseFollowup};
+use serenity::model::{
+	application::{CommandInteraction, CommandType, InstallationContext, ResolvedTarget},
+	colour::Colour,
+};
+use serenity::prelude::Context;
+
+/// The most PRs we'll check from a single message
+const MAX_PULL_REQUESTS: usize = 5;
+
+/// Longest title an embed can have
+const MAX_TITLE_LEN: usize = 256;
+
+pub async fn respond<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	// this will probably take a while
+	command.defer(&ctx).await?;
+
+	let Some(ResolvedTarget::Message(message)) = command.data.target() else {
+		let resp =
+			CreateInteractionResponseFollowup::new().content("I can only track PRs in messages!");
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	};
+
+	// someone picked this message on purpose, so `#123` probably means a PR
+	let numbers = references::find_pull_requests(&message.content, true);
+	if numbers.is_empty() {
+		let resp = CreateInteractionResponseFollowup::new()
+			.content("I couldn't find any nixpkgs PRs in that message");
+		command.create_followup(&ctx, resp).await?;
+
+		return Ok(());
+	}
+
+	let preferred = preferences::lookup(storage, command.guild_id, command.user.id)?;
+	let mut embeds = vec![];
+	for number in numbers.iter().take(MAX_PULL_REQUESTS) {
+		embeds.push(compact_status(http, config, storage, *number, preferred.as_deref()).await?);
+	}
+
+	let mut resp = CreateInteractionResponseFollowup::new().embeds(embeds);
+	if numbers.len() > MAX_PULL_REQUESTS {
+		resp = resp.content(format!(
+			"That message mentions {} PRs! Here are the first {MAX_PULL_REQUESTS}",
+			numbers.len()
+		));
+	}
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Describe where a PR has made it in a small embed
+///
+/// Problems with a single PR are shown in its embed, so they don't hide the others. Only
+/// branches matching `preferred` are shown, if it's set
+pub async fn compact_status<T>(
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	number: u64,
+	preferred: Option<&[String]>,
+) -> Result<CreateEmbed>
+where
+	T: GitHubClientExt,
+{
+	let pull_request = match super::pull_request(http, config, storage, number).await {
+		Ok(pull_request) => pull_request,
+		Err(why) => {
+			warn!("Couldn't look up PR #{number}!\n{why:?}");
+			return Ok(CreateEmbed::new()
+				.title(format!("Nixpkgs PR #{number}"))
+				.description("I couldn't find this PR")
+				.color(Colour::RED));
+		}
+	};
+
+	let title: String = format!("#{number}: {}", pull_request.title)
+		.chars()
+		.take(MAX_TITLE_LEN)
+		.collect();
+	let embed = CreateEmbed::new().title(title).url(&pull_request.html_url);
+
+	if !pull_request.merged {
+		return Ok(embed.description("Not merged yet"));
+	}
+
+	// seems older PRs may not have this
+	let Some(commit_sha) = &pull_request.merge_commit_sha else {
+		return Ok(embed.description("This PR is too old for me to track"));
+	};
+
+	let status =
+		Status::of_pull_request(http, config, storage, &pull_request, commit_sha, preferred)
+			.await?;
+	Ok(embed.description(status.summary()))
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("Track PRs in this message")
+		.kind(CommandType::Message)
+		.add_integration_type(InstallationContext::User)
+}
@@ -0,0 +1,144 @@
+use crate::{config::Config, links::Mode, storage::Storage};
+
+use eyre::Result;
+use serenity::builder::{CreateCommand, CreateCommandOption, CreateInteractionResponseFollowup};
+use serenity::model::{
+	application::{
+		CommandInteraction, CommandOptionType, InstallationContext, InteractionContext,
+		ResolvedOption, ResolvedValue,
+	},
+	channel::ChannelType,
+	id::ChannelId,
+	Permissions,
+};
+use serenity::prelude::Context;
+
+pub async fn respond(
+	ctx: &Context,
+	config: &Config,
+	storage: &Storage,
+	command: &CommandInteraction,
+) -> Result<()> {
+	command.defer_ephemeral(&ctx).await?;
+
+	let content = if !config.link_detection() {
+		"I'm not set up to read messages. Ask whoever runs me to set `BOT_LINK_DETECTION`!"
+			.to_string()
+	} else if let Some(guild_id) = command.guild_id {
+		let options = command.data.options();
+		match options.first() {
+			Some(ResolvedOption {
+				name: "add",
+				value: ResolvedValue::SubCommand(options),
+				..
+			}) => {
+				let (channel_id, mode) = channel_and_mode(options, command.channel_id);
+				storage
+					.lock()
+					.watched_channels()
+					.insert(guild_id, channel_id, mode)?;
+				match mode {
+					Mode::React => format!("I'll react to PRs mentioned in <#{channel_id}>"),
+					Mode::Reply => format!("I'll reply to PRs mentioned in <#{channel_id}>"),
+				}
+			}
+			Some(ResolvedOption {
+				name: "remove",
+				value: ResolvedValue::SubCommand(options),
+				..
+			}) => {
+				let (channel_id, _) = channel_and_mode(options, command.channel_id);
+				if storage.lock().watched_channels().remove(channel_id)? {
+					format!("I'll stop watching <#{channel_id}>")
+				} else {
+					format!("I wasn't watching <#{channel_id}>")
+				}
+			}
+			_ => {
+				let channels = storage.lock().watched_channels().in_guild(guild_id)?;
+				if channels.is_empty() {
+					"I'm not watching any channels here. Add one with `/watch add`".to_string()
+				} else {
+					channels
+						.iter()
+						.map(|(channel_id, mode)| format!("<#{channel_id}>: {}", mode.as_str()))
+						.collect::<Vec<_>>()
+						.join("\n")
+				}
+			}
+		}
+	} else {
+		"I can only watch channels in servers!".to_string()
+	};
+
+	let resp = CreateInteractionResponseFollowup::new().content(content);
+	command.create_followup(&ctx, resp).await?;
+
+	Ok(())
+}
+
+/// Find the channel and [`Mode`] a subcommand is for, defaulting to reacting in `current`
+fn channel_and_mode(options: &[ResolvedOption], current: ChannelId) -> (ChannelId, Mode) {
+	let mut channel_id = current;
+	let mut mode = Mode::React;
+	for option in options {
+		match (option.name, &option.value) {
+			("channel", ResolvedValue::Channel(channel)) => channel_id = channel.id,
+			("mode", ResolvedValue::String(value)) => mode = Mode::parse(value).unwrap_or(mode),
+			_ => {}
+		}
+	}
+
+	(channel_id, mode)
+}
+
+fn channel_option() -> CreateCommandOption {
+	CreateCommandOption::new(
+		CommandOptionType::Channel,
+		"channel",
+		"Channel to watch. Defaults to this one",
+	)
+	.channel_types(vec![
+		ChannelType::Text,
+		ChannelType::News,
+		ChannelType::PublicThread,
+	])
+}
+
+pub fn register() -> CreateCommand {
+	CreateCommand::new("watch")
+		.description("Watch a channel for nixpkgs PRs, and show how far they've made it")
+		.add_integration_type(InstallationContext::Guild)
+		.contexts(vec![InteractionContext::Guild])
+		.default_member_permissions(Permissions::MANAGE_GUILD)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::SubCommand,
+				"add",
+				"Start watching a channel",
+			)
+			.add_sub_option(channel_option())
+			.add_sub_option(
+				CreateCommandOption::new(
+					CommandOptionType::String,
+					"mode",
+					"How to respond to PRs",
+				)
+				.add_string_choice("React with an emoji", "react")
+				.add_string_choice("Reply with their status", "reply"),
+			),
+		)
+		.add_option(
+			CreateCommandOption::new(
+				CommandOptionType::SubCommand,
+				"remove",
+				"Stop watching a channel",
+			)
+			.add_sub_option(channel_option()),
+		)
+		.add_option(CreateCommandOption::new(
+			CommandOptionType::SubCommand,
+			"list",
+			"Show the channels being watched",
+		))
+}
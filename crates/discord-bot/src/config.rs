@@ -1,18 +1,53 @@
-use git_tracker::TrackedRepository;
+use crate::{
+	branches,
+	pipeline::{self, Pipelines},
+};
 
-use std::{env, path::PathBuf, sync::Arc};
+use git_tracker::{AsyncTrackedRepository, CloneMode, TrackedRepository};
+
+use std::{
+	env,
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, PoisonError, RwLock,
+	},
+};
+
+use eyre::{bail, Result};
+use log::info;
 
 const DEFAULT_NIXPKGS_URL: &str = "https://github.com/NixOS/nixpkgs";
 
 const DEFAULT_NIXPKGS_REMOTE: &str = "origin";
 
+/// How many Git operations we'll run at once by default
+const DEFAULT_GIT_CONCURRENCY: usize = 4;
+
+const DEFAULT_DATABASE_NAME: &str = "nixpkgs-tracker-bot.sqlite3";
+
 /// The Discord client's configuration
 #[derive(Clone, Debug)]
 pub struct Config {
-	/// Comma separated list of nixpkgs branch to track commits for
-	nixpkgs_branches: Vec<String>,
+	/// Names of (or glob patterns matching) the nixpkgs branches to track commits for
+	branch_patterns: Vec<String>,
+	/// How many of the newest stable releases to track branches of. All of them are tracked
+	/// when this isn't set
+	stable_releases: Option<usize>,
+	/// Branches currently matching `branch_patterns`, in the order we show them
+	nixpkgs_branches: Arc<RwLock<Vec<String>>>,
+	/// Whether `nixpkgs_branches` has been expanded against a repository we have
+	ready: Arc<AtomicBool>,
+	/// How changes move through our branches
+	pipelines: Pipelines,
+	/// Whether we fetch tags, and can tell which release a PR first made it into
+	fetch_tags: bool,
+	/// Whether we watch messages in channels that opt in for PRs
+	link_detection: bool,
 	/// Repository tracker
-	repository: Arc<TrackedRepository>,
+	repository: AsyncTrackedRepository,
+	/// Path to our database
+	database_path: PathBuf,
 }
 
 impl Config {
@@ -20,36 +55,178 @@ impl Config {
 	///
 	/// # Errors
 	///
-	/// Will return [`Err`] if a variable is not found
-	pub fn from_env() -> Result<Self, env::VarError> {
+	/// Will return [`Err`] if a variable is not found or is invalid
+	pub fn from_env() -> Result<Self> {
 		let nixpkgs_path = env::var("BOT_NIXPKGS_PATH")?;
 
-		let nixpkgs_branches = env::var("BOT_NIXPKGS_BRANCHES")?
+		let branch_patterns: Vec<String> = env::var("BOT_NIXPKGS_BRANCHES")?
 			.split(',')
 			.map(ToString::to_string)
 			.collect();
+		let stable_releases = env::var("BOT_NIXPKGS_STABLE_RELEASES")
+			.ok()
+			.map(|value| value.parse())
+			.transpose()?;
+
+		let pipelines = Pipelines::parse(
+			&env::var("BOT_PIPELINE_UNSTABLE")
+				.unwrap_or_else(|_| pipeline::DEFAULT_UNSTABLE.to_string()),
+			&env::var("BOT_PIPELINE_RELEASE")
+				.unwrap_or_else(|_| pipeline::DEFAULT_RELEASE.to_string()),
+		)?;
 
 		let nixpkgs_remote =
 			env::var("BOT_NIXPKGS_REMOTE").unwrap_or(DEFAULT_NIXPKGS_REMOTE.to_string());
 		let nixpkgs_url = env::var("BOT_NIXPKGS_URL").unwrap_or(DEFAULT_NIXPKGS_URL.to_string());
+		let clone_mode = match env::var("BOT_NIXPKGS_CLONE_MODE").as_deref() {
+			Err(_) | Ok("full") => CloneMode::Full,
+			Ok("blobless") => CloneMode::Blobless,
+			Ok(other) => bail!("Unknown clone mode `{other}`! Expected `full` or `blobless`"),
+		};
+		let fetch_tags = env::var("BOT_NIXPKGS_TAGS")
+			.ok()
+			.map(|value| value.parse())
+			.transpose()?
+			.unwrap_or_default();
+		let link_detection = env::var("BOT_LINK_DETECTION")
+			.ok()
+			.map(|value| value.parse())
+			.transpose()?
+			.unwrap_or_default();
+		let git_concurrency = match env::var("BOT_GIT_CONCURRENCY") {
+			Ok(value) => value.parse()?,
+			Err(_) => DEFAULT_GIT_CONCURRENCY,
+		};
+
+		// keep our state right next to the repository by default
+		let database_path = env::var("BOT_DATABASE_PATH").map_or_else(
+			|_| {
+				PathBuf::from(&nixpkgs_path)
+					.parent()
+					.unwrap_or(Path::new("."))
+					.join(DEFAULT_DATABASE_NAME)
+			},
+			PathBuf::from,
+		);
 
-		let repository = TrackedRepository::new(
+		let mut repository = TrackedRepository::new(
 			PathBuf::from(nixpkgs_path.clone()),
 			nixpkgs_url,
 			nixpkgs_remote,
+		)
+		.with_branches(branch_patterns.clone())
+		.with_clone_mode(clone_mode)
+		.with_tags(fetch_tags);
+		// there's no point in fetching branches we'll never show
+		if let Some(count) = stable_releases {
+			repository = repository
+				.with_branch_filter(move |branches| branches::newest_releases(branches, count));
+		}
+
+		// patterns can't be expanded until we've fetched, so start with what we know exists
+		let nixpkgs_branches = branches::select(
+			&branch_patterns,
+			branch_patterns
+				.iter()
+				.filter(|branch| !branches::is_pattern(branch))
+				.cloned()
+				.collect(),
+			stable_releases,
 		);
 
 		Ok(Self {
-			nixpkgs_branches,
-			repository: Arc::new(repository),
+			branch_patterns,
+			stable_releases,
+			nixpkgs_branches: Arc::new(RwLock::new(nixpkgs_branches)),
+			ready: Arc::default(),
+			pipelines,
+			fetch_tags,
+			link_detection,
+			repository: AsyncTrackedRepository::new(repository, git_concurrency),
+			database_path,
 		})
 	}
 
-	pub fn repository(&self) -> &TrackedRepository {
+	pub fn repository(&self) -> &AsyncTrackedRepository {
 		&self.repository
 	}
 
-	pub fn nixpkgs_branches(&self) -> &Vec<String> {
-		&self.nixpkgs_branches
+	/// Get the branches we're currently tracking
+	pub fn nixpkgs_branches(&self) -> Vec<String> {
+		self.nixpkgs_branches
+			.read()
+			.unwrap_or_else(PoisonError::into_inner)
+			.clone()
+	}
+
+	pub fn pipelines(&self) -> &Pipelines {
+		&self.pipelines
+	}
+
+	/// Get the branches worth checking for a PR merged into `base`
+	///
+	/// These are the branches after `base` in its pipeline, or every branch we track if it
+	/// isn't part of one
+	pub fn downstream_branches(&self, base: &str) -> Vec<String> {
+		let branches = self.nixpkgs_branches();
+		self.pipelines
+			.downstream(base, &branches)
+			.unwrap_or(branches)
+	}
+
+	/// Get the branches a PR merged into `base` might have been backported to, and the ones
+	/// following them
+	pub fn backport_branches(&self, base: &str) -> Vec<String> {
+		let branches = self.nixpkgs_branches();
+		self.pipelines
+			.backport_targets(base, &branches)
+			.iter()
+			.flat_map(|target| {
+				self.pipelines
+					.downstream(target, &branches)
+					.unwrap_or_default()
+			})
+			.collect()
+	}
+
+	/// Check if we have a repository, and know which of its branches we're tracking
+	///
+	/// Until then, commands would only find nothing
+	pub fn is_ready(&self) -> bool {
+		self.ready.load(Ordering::SeqCst)
+	}
+
+	/// Expand our branch patterns against the branches in the repository
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository's branches can't be listed
+	pub async fn refresh_branches(&self) -> Result<()> {
+		let available = self.repository.tracked_branches().await?;
+		let selected = branches::select(&self.branch_patterns, available, self.stable_releases);
+
+		let mut nixpkgs_branches = self
+			.nixpkgs_branches
+			.write()
+			.unwrap_or_else(PoisonError::into_inner);
+		if *nixpkgs_branches != selected {
+			info!("Now tracking branches {}", selected.join(", "));
+			*nixpkgs_branches = selected;
+		}
+		self.ready.store(true, Ordering::SeqCst);
+
+		Ok(())
+	}
+
+	pub fn fetch_tags(&self) -> bool {
+		self.fetch_tags
+	}
+
+	pub fn link_detection(&self) -> bool {
+		self.link_detection
+	}
+
+	pub fn database_path(&self) -> &Path {
+		&self.database_path
 	}
 }
@@ -1,4 +1,7 @@
-use crate::{commands, SharedConfig, SharedHttp};
+use crate::{commands, config::Config, http, links, storage::Storage};
+use crate::{SharedConfig, SharedHttp, SharedStorage};
+
+use std::sync::Arc;
 
 use eyre::{OptionExt, Result};
 use log::{debug, error, info, trace, warn};
@@ -9,12 +12,27 @@ use serenity::builder::{
 	CreateInteractionResponseMessage,
 };
 use serenity::model::{
-	application::{Command, CommandInteraction, Interaction},
+	application::{Command, CommandInteraction, ComponentInteraction, Interaction},
+	channel::Message,
 	colour::Colour,
 	gateway::Ready,
 };
 use serenity::prelude::{Context, EventHandler};
 
+/// Commands that can't do anything until we have a repository
+const NEEDS_REPOSITORY: &[&str] = &[
+	"channels",
+	"commit",
+	"subscribe",
+	"track",
+	"track-commit",
+	"Track PRs in this message",
+];
+
+/// What we say when we're asked for something before we have a repository
+const NOT_READY: &str =
+	"I'm still cloning nixpkgs and finding the branches I track. Try again in a few minutes!";
+
 #[derive(Clone, Copy, Debug)]
 pub struct Handler;
 
@@ -26,32 +44,62 @@ impl Handler {
 			Command::create_global_command(&ctx.http, command).await?;
 		}
 
-		debug!("Registered {} commands", commands_len);
+		debug!("Registered {commands_len} commands");
 		Ok(())
 	}
 
+	/// Grab our configuration, http client, & storage from the aether
+	async fn shared_state(ctx: &Context) -> Result<(Arc<http::Client>, Arc<Config>, Arc<Storage>)> {
+		let read = ctx.data.read().await;
+		let http = read
+			.get::<SharedHttp>()
+			.ok_or_eyre("Couldn't get shared HTTP client! WHY??????")?
+			.clone();
+		let config = read
+			.get::<SharedConfig>()
+			.ok_or_eyre("Couldn't get shared bot configuration!")?
+			.clone();
+		let storage = read
+			.get::<SharedStorage>()
+			.ok_or_eyre("Couldn't get shared storage!")?
+			.clone();
+
+		Ok((http, config, storage))
+	}
+
 	/// Dispatch our commands from a [`CommandInteraction`]
 	async fn dispatch_command(ctx: &Context, command: &CommandInteraction) -> Result<()> {
 		let command_name = command.data.name.as_str();
+		let (http, config, storage) = Handler::shared_state(ctx).await?;
 
-		// grab our configuration & http client from the aether
-		let (http, config) = {
-			let read = ctx.data.read().await;
-			let http = read
-				.get::<SharedHttp>()
-				.ok_or_eyre("Couldn't get shared HTTP client! WHY??????")?
-				.clone();
-			let config = read
-				.get::<SharedConfig>()
-				.ok_or_eyre("Couldn't get shared bot configuration!")?
-				.clone();
-			(http, config)
-		};
+		if NEEDS_REPOSITORY.contains(&command_name) && !config.is_ready() {
+			let message = CreateInteractionResponseMessage::new()
+				.ephemeral(true)
+				.content(NOT_READY);
+			command
+				.create_response(&ctx, CreateInteractionResponse::Message(message))
+				.await?;
+
+			return Ok(());
+		}
 
 		match command_name {
 			"about" => commands::about::respond(ctx, &http, command).await?,
+			"channels" => commands::channels::respond(ctx, &config, &storage, command).await?,
+			"commit" => commands::commit::respond(ctx, &http, &config, &storage, command).await?,
+			"config" => commands::config::respond(ctx, &config, &storage, command).await?,
 			"ping" => commands::ping::respond(ctx, command).await?,
-			"track" => commands::track::respond(ctx, &http, &config, command).await?,
+			"subscribe" => {
+				commands::subscribe::respond(ctx, &http, &config, &storage, command).await?;
+			}
+			"track" => commands::track::respond(ctx, &http, &config, &storage, command).await?,
+			"track-commit" => {
+				commands::track_commit::respond(ctx, &config, &storage, command).await?;
+			}
+			"Track PRs in this message" => {
+				commands::track_message::respond(ctx, &http, &config, &storage, command).await?;
+			}
+			"watch" => commands::watch::respond(ctx, &config, &storage, command).await?,
 			_ => {
 				let message = CreateInteractionResponseMessage::new().content(format!(
 					"It doesn't look like you can use `{command_name}`. Sorry :("
@@ -59,7 +107,44 @@ impl Handler {
 				let response = CreateInteractionResponse::Message(message);
 				command.create_response(&ctx, response).await?;
 			}
-		};
+		}
+
+		Ok(())
+	}
+
+	/// Dispatch autocompletion for our commands' options
+	async fn dispatch_autocomplete(ctx: &Context, interaction: &CommandInteraction) -> Result<()> {
+		let (_, config, _) = Handler::shared_state(ctx).await?;
+
+		match interaction.data.name.as_str() {
+			"track" => commands::track::autocomplete(ctx, &config, interaction).await?,
+			command_name => debug!("Got autocomplete request for unknown command {command_name}"),
+		}
+
+		Ok(())
+	}
+
+	/// Dispatch clicks on our messages' components from a [`ComponentInteraction`]
+	async fn dispatch_component(ctx: &Context, component: &ComponentInteraction) -> Result<()> {
+		let (http, config, storage) = Handler::shared_state(ctx).await?;
+
+		if !config.is_ready() {
+			let message = CreateInteractionResponseMessage::new()
+				.ephemeral(true)
+				.content(NOT_READY);
+			component
+				.create_response(&ctx, CreateInteractionResponse::Message(message))
+				.await?;
+
+			return Ok(());
+		}
+
+		match component.data.custom_id.split(':').next() {
+			Some(commands::track::COMPONENT_PREFIX) => {
+				commands::track::handle_component(ctx, &http, &config, &storage, component).await?;
+			}
+			_ => debug!("Got unknown component {}", component.data.custom_id),
+		}
 
 		Ok(())
 	}
@@ -76,28 +161,76 @@ impl Handler {
 
 #[async_trait]
 impl EventHandler for Handler {
-	/// Dispatch our commands and try to handle errors from them
+	/// Dispatch our interactions and try to handle errors from them
 	async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-		if let Interaction::Command(command) = interaction {
-			let command_name = &command.data.name;
-			trace!("Received command: {}", command_name);
-
-			if let Err(why) = Handler::dispatch_command(&ctx, &command).await {
-				error!(
-					"Ran into an error while dispatching command {}:\n{why:?}",
-					command_name
-				);
-
-				let embed = CreateEmbed::new()
-					.title("An error occurred")
-					.description("Sorry about that!")
-					.color(Colour::RED);
-				let response = CreateInteractionResponseFollowup::new().embed(embed);
-
-				if let Err(why) = command.create_followup(&ctx.http, response).await {
-					error!("Ran into an error while trying to recover from an error!\n{why:?}");
+		match interaction {
+			Interaction::Command(command) => {
+				let command_name = &command.data.name;
+				trace!("Received command: {command_name}");
+
+				if let Err(why) = Handler::dispatch_command(&ctx, &command).await {
+					error!("Ran into an error while dispatching command {command_name}:\n{why:?}");
+
+					let embed = CreateEmbed::new()
+						.title("An error occurred")
+						.description("Sorry about that!")
+						.color(Colour::RED);
+					let response = CreateInteractionResponseFollowup::new().embed(embed);
+
+					if let Err(why) = command.create_followup(&ctx.http, response).await {
+						error!("Ran into an error while trying to recover from an error!\n{why:?}");
+					}
 				}
 			}
+			Interaction::Component(component) => {
+				let custom_id = &component.data.custom_id;
+				trace!("Received component: {custom_id}");
+
+				if let Err(why) = Handler::dispatch_component(&ctx, &component).await {
+					error!("Ran into an error while handling component {custom_id}:\n{why:?}");
+
+					let embed = CreateEmbed::new()
+						.title("An error occurred")
+						.description("Sorry about that!")
+						.color(Colour::RED);
+					let response = CreateInteractionResponseFollowup::new()
+						.ephemeral(true)
+						.embed(embed);
+
+					if let Err(why) = component.create_followup(&ctx.http, response).await {
+						error!("Ran into an error while trying to recover from an error!\n{why:?}");
+					}
+				}
+			}
+			Interaction::Autocomplete(interaction) => {
+				// there's no way to show an error here, so just log it
+				if let Err(why) = Handler::dispatch_autocomplete(&ctx, &interaction).await {
+					error!(
+						"Ran into an error while autocompleting for {}:\n{why:?}",
+						interaction.data.name
+					);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Look for PRs in messages sent to channels we watch
+	async fn message(&self, ctx: Context, new_message: Message) {
+		let result = match Handler::shared_state(&ctx).await {
+			// nothing could be found yet anyway
+			Ok((_, config, _)) if !config.is_ready() => Ok(()),
+			Ok((http, config, storage)) => {
+				links::detect(&ctx, &http, &config, &storage, &new_message).await
+			}
+			Err(why) => Err(why),
+		};
+
+		if let Err(why) = result {
+			error!(
+				"Ran into an error while looking for PRs in message {}:\n{why:?}",
+				new_message.id
+			);
 		}
 	}
 
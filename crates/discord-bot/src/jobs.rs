@@ -1,34 +1,137 @@
-use crate::config::Config;
+use crate::{config::Config, storage::Storage, subscriptions};
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use eyre::Result;
-use log::error;
+use git_tracker::FetchReport;
+use log::{error, info, warn};
+use serenity::http::Http;
 
 const TTL_SECS: u64 = 60 * 5; // 5 minutes
 
-/// Run our jobs an initial time, then loop them on a separate thread
-///
-/// # Errors
-///
-/// Will return [`Err`] if any jobs fail
-pub fn dispatch(config: &Config) -> Result<()> {
-	let repository = config.repository();
-	if repository.open().is_err() {
-		repository.clone_repository()?;
-	}
-	repository.fetch()?;
-
-	let repository_clone = repository.clone();
+/// Run our jobs on a separate task, starting right away and then looping
+pub fn dispatch(config: &Config, http: Arc<Http>, storage: Arc<Storage>) {
+	let config = config.clone();
 
 	tokio::spawn(async move {
+		// make sure we're starting with something that works
+		if config.repository().exists().await {
+			repair_repository(&config).await;
+			// what we already have is good enough to start answering commands
+			if let Err(why) = config.refresh_branches().await {
+				error!("Could not load tracked branches!\n{why:?}");
+			}
+		}
+
 		loop {
+			match update_repository(&config).await {
+				Ok(report) => {
+					log_report(&report);
+					if let Err(why) = record_advances(&storage, &report) {
+						error!("Could not record branch advances!\n{why:?}");
+					}
+
+					// new releases (and deleted ones) only show up after a fetch
+					if let Err(why) = config.refresh_branches().await {
+						error!("Could not refresh tracked branches!\n{why:?}");
+					}
+
+					// keep our fallback for when GitHub is unavailable up to date
+					let pipelines = config.pipelines().clone();
+					if let Err(why) = config
+						.repository()
+						.update_pull_request_index(move |branch| pipelines.stage(branch))
+						.await
+					{
+						error!("Could not update PR index!\n{why:?}");
+					}
+
+					// if nothing changed, nothing could have reached a new branch
+					if !report.updates.is_empty() {
+						if let Err(why) = subscriptions::notify(&http, &config, &storage).await {
+							error!("Could not notify subscribers!\n{why:?}");
+						}
+					}
+				}
+				Err(why) => error!("Could not fetch or update repository!\n{why:?}"),
+			}
+
 			tokio::time::sleep(Duration::from_secs(TTL_SECS)).await;
-			if let Err(why) = repository_clone.fetch() {
-				error!("Could not fetch or update repository!\n{why:?}");
-			};
 		}
 	});
+}
+
+/// Clone our repository if we don't have it yet, or fetch it if we do
+async fn update_repository(config: &Config) -> Result<FetchReport> {
+	let repository = config.repository();
+	let result = if repository.exists().await {
+		repository.fetch().await
+	} else {
+		repository.clone_repository().await
+	};
+
+	match result {
+		Ok(report) => Ok(report),
+		Err(why) => {
+			// something might be broken. try to fix it before giving up
+			warn!("Couldn't update repository, checking its health\n{why:?}");
+			if repair_repository(config).await {
+				Ok(repository.fetch().await?)
+			} else {
+				Err(why.into())
+			}
+		}
+	}
+}
+
+/// Fix any problems with our repository, returning whether there were any
+async fn repair_repository(config: &Config) -> bool {
+	match config.repository().repair().await {
+		Ok(problems) => {
+			for problem in &problems {
+				info!("Repaired repository: {problem}");
+			}
+
+			!problems.is_empty()
+		}
+		Err(why) => {
+			error!("Couldn't repair repository!\n{why:?}");
+			false
+		}
+	}
+}
+
+/// Remember when we saw branches move forward, so we can tell when one is stuck or when a
+/// channel picked up a change
+fn record_advances(storage: &Storage, report: &FetchReport) -> Result<()> {
+	let storage = storage.lock();
+	let advances = storage.branch_advances();
+	for update in &report.updates {
+		// new branches (including everything in a fresh clone) haven't advanced yet
+		if update.is_created() {
+			continue;
+		}
+
+		if let (Some(branch), Some(old), Some(new)) = (update.branch(), update.old, update.new) {
+			advances.record(branch, &old.to_string(), &new.to_string())?;
+		}
+	}
 
 	Ok(())
 }
+
+/// Log the statistics of a fetch
+fn log_report(report: &FetchReport) {
+	if report.coalesced {
+		info!("Another fetch just finished, so we didn't fetch again");
+		return;
+	}
+
+	info!(
+		"Fetched {} objects ({} bytes) and updated {} refs in {}ms",
+		report.stats.received_objects,
+		report.stats.received_bytes,
+		report.updates.len(),
+		report.duration.as_millis()
+	);
+}
@@ -4,14 +4,22 @@ use eyre::Result;
 use log::trace;
 use serenity::prelude::{Client, GatewayIntents, TypeMapKey};
 
+mod branches;
 mod commands;
 mod config;
 mod handler;
 mod jobs;
+mod links;
+mod pipeline;
+mod preferences;
+mod references;
+mod storage;
+mod subscriptions;
 
 use config::Config;
 use handler::Handler;
 use nixpkgs_tracker_http as http;
+use storage::Storage;
 
 /// Container for [`http::Client`]
 struct SharedHttp;
@@ -27,6 +35,13 @@ impl TypeMapKey for SharedConfig {
 	type Value = Arc<Config>;
 }
 
+/// Container for [`Storage`]
+struct SharedStorage;
+
+impl TypeMapKey for SharedStorage {
+	type Value = Arc<Storage>;
+}
+
 /// Fetch our bot token
 fn token() -> Result<String> {
 	let token = std::env::var("DISCORD_BOT_TOKEN")?;
@@ -46,7 +61,13 @@ fn token() -> Result<String> {
 pub async fn client() -> Result<Client> {
 	let token = token().expect("Couldn't find token in environment! Is DISCORD_BOT_TOKEN set?");
 
-	let intents = GatewayIntents::default();
+	let config = Config::from_env()?;
+
+	let mut intents = GatewayIntents::default();
+	// reading messages is a privileged intent, so only ask for it when we need it
+	if config.link_detection() {
+		intents |= GatewayIntents::GUILD_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+	}
 	trace!("Creating client");
 	let client = Client::builder(token, intents)
 		.event_handler(Handler)
@@ -54,13 +75,14 @@ pub async fn client() -> Result<Client> {
 
 	// add state stuff
 	let http_client = <http::Client as http::Ext>::default();
-	let config = Config::from_env()?;
+	let storage = Arc::new(Storage::open(config.database_path())?);
 
 	{
 		let mut data = client.data.write().await;
 
 		data.insert::<SharedHttp>(Arc::new(http_client));
 		data.insert::<SharedConfig>(Arc::new(config.clone()));
+		data.insert::<SharedStorage>(storage.clone());
 	}
 
 	let shard_manager = client.shard_manager.clone();
@@ -75,7 +97,7 @@ pub async fn client() -> Result<Client> {
 	});
 
 	// run our jobs
-	jobs::dispatch(&config)?;
+	jobs::dispatch(&config, client.http.clone(), storage);
 
 	Ok(client)
 }
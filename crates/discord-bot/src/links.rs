@@ -0,0 +1,218 @@
+//! Noticing nixpkgs PRs mentioned in the channels we watch, and responding to them
+use crate::{
+	commands::{self, track::Status, track_message},
+	config::Config,
+	http::GitHubClientExt,
+	preferences, references,
+	storage::Storage,
+};
+
+use std::sync::Arc;
+
+use eyre::Result;
+use log::{debug, trace, warn};
+use serenity::builder::{CreateAllowedMentions, CreateMessage};
+use serenity::model::channel::{Message, ReactionType};
+use serenity::prelude::Context;
+
+/// The most PRs we'll check from a single message. Nobody asked us to, so keep it short
+const MAX_PULL_REQUESTS: usize = 3;
+
+/// How we respond to PRs mentioned in a channel we watch
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+	/// React to the message with how far its PRs have made it
+	React,
+	/// Reply to the message with the status of its PRs
+	Reply,
+}
+
+impl Mode {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::React => "react",
+			Self::Reply => "reply",
+		}
+	}
+
+	pub fn parse(value: &str) -> Option<Self> {
+		match value {
+			"react" => Some(Self::React),
+			"reply" => Some(Self::Reply),
+			_ => None,
+		}
+	}
+}
+
+/// How far a PR has made it, from least to most
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Landed {
+	/// The PR hasn't been merged
+	NotMerged,
+	/// The PR has been merged, but hasn't reached every branch yet
+	Partially,
+	/// The PR has reached every branch we track
+	Everywhere,
+}
+
+impl Landed {
+	fn emoji(self) -> char {
+		match self {
+			Self::NotMerged => '🚧',
+			Self::Partially => '⏳',
+			Self::Everywhere => '🎉',
+		}
+	}
+}
+
+/// Respond to nixpkgs PRs mentioned in a message, if it was sent in a channel we watch
+///
+/// # Errors
+///
+/// Will return [`Err`] if the database can't be used or we can't respond
+pub async fn detect<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	message: &Message,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	if !config.link_detection() || message.author.bot || message.guild_id.is_none() {
+		return Ok(());
+	}
+
+	let Some(mode) = storage.lock().watched_channels().get(message.channel_id)? else {
+		return Ok(());
+	};
+
+	// we weren't asked about this message, so only look for things that are surely PRs
+	let numbers = references::find_pull_requests(&message.content, false);
+	if numbers.is_empty() {
+		return Ok(());
+	}
+
+	// the gateway can send the same message more than once, e.g. after resuming a session
+	if !storage.lock().seen_messages().insert(message.id)? {
+		trace!("Already handled message {}, skipping", message.id);
+		return Ok(());
+	}
+
+	debug!(
+		"Found PRs {numbers:?} in message {} in channel {}",
+		message.id, message.channel_id
+	);
+	let numbers = &numbers[..numbers.len().min(MAX_PULL_REQUESTS)];
+	let preferred = preferences::lookup(storage, message.guild_id, message.author.id)?;
+	let preferred = preferred.as_deref();
+	match mode {
+		Mode::React => react(ctx, http, config, storage, message, numbers, preferred).await,
+		Mode::Reply => reply(ctx, http, config, storage, message, numbers, preferred).await,
+	}
+}
+
+/// React to a message with how far the least progressed of its PRs has made it
+async fn react<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	message: &Message,
+	numbers: &[u64],
+	preferred: Option<&[String]>,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	let mut progress = vec![];
+	for number in numbers {
+		match landed(http, config, storage, *number, preferred).await {
+			Ok(Some(landed)) => progress.push(landed),
+			Ok(None) => {}
+			Err(why) => warn!("Couldn't check PR #{number}!\n{why:?}"),
+		}
+	}
+
+	if let Some(landed) = progress.into_iter().min() {
+		message
+			.react(&ctx, ReactionType::Unicode(landed.emoji().to_string()))
+			.await?;
+	}
+
+	Ok(())
+}
+
+/// Reply to a message with the status of each of its PRs
+async fn reply<T>(
+	ctx: &Context,
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	message: &Message,
+	numbers: &[u64],
+	preferred: Option<&[String]>,
+) -> Result<()>
+where
+	T: GitHubClientExt,
+{
+	let mut embeds = vec![];
+	for number in numbers {
+		match track_message::compact_status(http, config, storage, *number, preferred).await {
+			Ok(embed) => embeds.push(embed),
+			Err(why) => warn!("Couldn't check PR #{number}!\n{why:?}"),
+		}
+	}
+
+	if embeds.is_empty() {
+		return Ok(());
+	}
+
+	let reply = CreateMessage::new()
+		.reference_message(message)
+		// nobody needs a ping for this
+		.allowed_mentions(CreateAllowedMentions::new().replied_user(false))
+		.embeds(embeds);
+	// nobody asked us to reply, so there's no one to tell if we can't
+	if let Err(why) = message.channel_id.send_message(&ctx, reply).await {
+		warn!(
+			"Couldn't reply to message {} in channel {}!\n{why:?}",
+			message.id, message.channel_id
+		);
+	}
+
+	Ok(())
+}
+
+/// Find out how far a PR has made it, if we can track it at all
+async fn landed<T>(
+	http: &Arc<T>,
+	config: &Config,
+	storage: &Storage,
+	number: u64,
+	preferred: Option<&[String]>,
+) -> Result<Option<Landed>>
+where
+	T: GitHubClientExt,
+{
+	let pull_request = commands::pull_request(http, config, storage, number).await?;
+	if !pull_request.merged {
+		return Ok(Some(Landed::NotMerged));
+	}
+
+	let Some(commit_sha) = &pull_request.merge_commit_sha else {
+		return Ok(None);
+	};
+
+	let status =
+		Status::of_pull_request(http, config, storage, &pull_request, commit_sha, preferred)
+			.await?;
+	let landed = if status.reached_everywhere() {
+		Landed::Everywhere
+	} else {
+		Landed::Partially
+	};
+
+	Ok(Some(landed))
+}
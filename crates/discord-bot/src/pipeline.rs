@@ -0,0 +1,512 @@
+//! How changes are promoted through nixpkgs' branches
+use crate::branches::{self, Release};
+
+use eyre::{bail, Result};
+
+/// How changes move through the unstable branches by default
+///
+/// Both `nixpkgs-unstable` and `nixos-unstable-small` advance from `master` on their own
+pub const DEFAULT_UNSTABLE: &str = "staging > staging-next > master > nixpkgs-unstable; \
+	master > nixos-unstable-small > nixos-unstable";
+
+/// How changes move through a stable release's branches by default. `{release}` is replaced with
+/// its version
+pub const DEFAULT_RELEASE: &str = "staging-{release} > staging-next-{release} > \
+	release-{release} > nixpkgs-{release}-darwin; \
+	release-{release} > nixos-{release}-small > nixos-{release}";
+
+/// The stage of a stable release's pipeline backports are merged into
+const BACKPORT_STAGE: &str = "release-{release}";
+
+/// Check if a branch is a channel (i.e., `nixos-unstable` or `nixpkgs-24.05-darwin`)
+///
+/// Channels are fast-forwarded to commits that have been built, rather than merged into
+pub fn is_channel(branch: &str) -> bool {
+	branch.starts_with("nixos-") || branch.starts_with("nixpkgs-")
+}
+
+/// Stages of a pipeline, each with the index of the stage it follows
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Layout {
+	stages: Vec<String>,
+	follows: Vec<Option<usize>>,
+}
+
+/// Check if a change reaching the stage at `ancestor` will eventually reach the one at `index`,
+/// given the stage each one `follows`
+fn leads_to(follows: &[Option<usize>], ancestor: usize, mut index: usize) -> bool {
+	loop {
+		if index == ancestor {
+			return true;
+		}
+		match follows[index] {
+			Some(upstream) => index = upstream,
+			None => return false,
+		}
+	}
+}
+
+impl Layout {
+	/// Parse a layout like `a > b > c; b > d`
+	///
+	/// Stages follow the one before them. Chains are separated by `;`, and branch off from
+	/// wherever their first stage was already listed
+	fn parse(spec: &str) -> Result<Self> {
+		let mut stages: Vec<String> = vec![];
+		let mut follows = vec![];
+		for chain in spec.split(';') {
+			let mut previous = None;
+			for (position, stage) in chain.split('>').map(str::trim).enumerate() {
+				if stage.is_empty() {
+					bail!("Pipeline `{spec}` has an empty stage");
+				}
+
+				let existing = stages.iter().position(|name| name == stage);
+				match existing {
+					// the start of a chain can be an earlier stage it branches off from
+					Some(index) if position == 0 => previous = Some(index),
+					Some(_) => bail!("Pipeline `{spec}` lists `{stage}` more than once"),
+					None => {
+						stages.push(stage.to_string());
+						follows.push(previous);
+						previous = Some(stages.len() - 1);
+					}
+				}
+			}
+		}
+
+		if stages.is_empty() {
+			bail!("Pipeline `{spec}` has no stages");
+		}
+
+		Ok(Self { stages, follows })
+	}
+
+	/// Fill in the version of a release
+	fn for_release(&self, release: &str) -> Self {
+		Self {
+			stages: self
+				.stages
+				.iter()
+				.map(|stage| stage.replace("{release}", release))
+				.collect(),
+			follows: self.follows.clone(),
+		}
+	}
+
+	fn position(&self, branch: &str) -> Option<usize> {
+		self.stages.iter().position(|stage| stage == branch)
+	}
+
+	/// Count how many stages come before a stage
+	fn depth(&self, mut index: usize) -> usize {
+		let mut depth = 0;
+		while let Some(upstream) = self.follows[index] {
+			depth += 1;
+			index = upstream;
+		}
+
+		depth
+	}
+}
+
+/// The pipelines changes move through, before they're narrowed down to the branches we track
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipelines {
+	unstable: Layout,
+	release: Layout,
+}
+
+impl Default for Pipelines {
+	fn default() -> Self {
+		Self::parse(DEFAULT_UNSTABLE, DEFAULT_RELEASE)
+			.expect("Default pipelines should always be valid!")
+	}
+}
+
+/// An ordered list of branches changes move through
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+	/// What to call this pipeline (i.e., `unstable` or `24.05`)
+	pub name: String,
+	/// Branches in this pipeline, each after the one it follows
+	pub stages: Vec<String>,
+	/// The index in `stages` of the stage each one follows, if any
+	follows: Vec<Option<usize>>,
+}
+
+/// How far a change has made it through a [`Pipeline`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Progress<'a> {
+	/// The last stages the change has reached on each path through the pipeline
+	pub furthest: Vec<&'a str>,
+	/// The stages we expect the change to reach next
+	pub next: Vec<&'a str>,
+}
+
+/// Format a [`Release`] the way nixpkgs does (i.e., `24.05`)
+fn version((year, month): Release) -> String {
+	format!("{year:02}.{month:02}")
+}
+
+impl Pipeline {
+	/// Narrow a [`Layout`] down to the `tracked` branches
+	///
+	/// Stages following one we don't track follow whichever stage before it we do
+	fn new(name: String, layout: &Layout, tracked: &[String]) -> Self {
+		let mut indices = vec![None; layout.stages.len()];
+		let mut stages = vec![];
+		let mut follows = vec![];
+		for (index, stage) in layout.stages.iter().enumerate() {
+			if !tracked.contains(stage) {
+				continue;
+			}
+
+			let mut upstream = layout.follows[index];
+			while let Some(current) = upstream {
+				if indices[current].is_some() {
+					break;
+				}
+				upstream = layout.follows[current];
+			}
+
+			indices[index] = Some(stages.len());
+			follows.push(upstream.and_then(|upstream| indices[upstream]));
+			stages.push(stage.clone());
+		}
+
+		Self {
+			name,
+			stages,
+			follows,
+		}
+	}
+
+	/// Group branches that don't follow each other
+	pub fn flat(name: String, stages: Vec<String>) -> Self {
+		Self {
+			name,
+			follows: vec![None; stages.len()],
+			stages,
+		}
+	}
+
+	/// Check if a branch is part of this pipeline
+	pub fn contains(&self, branch: &str) -> bool {
+		self.stages.iter().any(|stage| stage == branch)
+	}
+
+	/// Find how far a change has made it, given whether it has `reached` each stage
+	///
+	/// Changes don't have to start at the first stage (i.e., PRs merged straight into `master`),
+	/// so only the last stages reached matter
+	pub fn progress(&self, reached: impl Fn(&str) -> bool) -> Progress<'_> {
+		let reached: Vec<_> = self.stages.iter().map(|stage| reached(stage)).collect();
+		let ahead = |index: usize| {
+			(0..self.stages.len()).any(|other| {
+				other != index && reached[other] && leads_to(&self.follows, index, other)
+			})
+		};
+
+		let furthest = (0..self.stages.len())
+			.filter(|&index| reached[index] && !ahead(index))
+			.map(|index| self.stages[index].as_str())
+			.collect();
+		let nothing_reached = !reached.contains(&true);
+		let next = (0..self.stages.len())
+			.filter(|&index| {
+				!reached[index]
+					&& !ahead(index)
+					&& match self.follows[index] {
+						Some(follows) => reached[follows],
+						None => nothing_reached,
+					}
+			})
+			.map(|index| self.stages[index].as_str())
+			.collect();
+
+		Progress { furthest, next }
+	}
+
+	/// Split the stages into chains of ones following each other
+	///
+	/// Chains after the first start with the stage they branch off from
+	pub fn chains(&self) -> Vec<Vec<&str>> {
+		let mut chains: Vec<Vec<&str>> = vec![];
+		for (index, stage) in self.stages.iter().enumerate() {
+			let follows = self.follows[index];
+			let continues = index > 0 && follows == Some(index - 1);
+			match chains.last_mut() {
+				Some(chain) if continues => chain.push(stage),
+				_ => {
+					let mut chain: Vec<_> = follows
+						.map(|follows| self.stages[follows].as_str())
+						.into_iter()
+						.collect();
+					chain.push(stage);
+					chains.push(chain);
+				}
+			}
+		}
+
+		chains
+	}
+}
+
+/// Check if backports are merged into a branch
+pub fn takes_backports(branch: &str) -> bool {
+	branches::release(branch)
+		.is_some_and(|release| branch == BACKPORT_STAGE.replace("{release}", &version(release)))
+}
+
+impl Pipelines {
+	/// Parse the pipelines of unstable and stable releases, like [`DEFAULT_UNSTABLE`] and
+	/// [`DEFAULT_RELEASE`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if either has empty or repeated stages
+	pub fn parse(unstable: &str, release: &str) -> Result<Self> {
+		Ok(Self {
+			unstable: Layout::parse(unstable)?,
+			release: Layout::parse(release)?,
+		})
+	}
+
+	/// Get every stage of the pipeline a branch would be part of
+	fn layout_of(&self, branch: &str) -> Layout {
+		if let Some(release) = branches::release(branch) {
+			self.release.for_release(&version(release))
+		} else {
+			self.unstable.clone()
+		}
+	}
+
+	/// Build the pipelines made up of the `tracked` branches
+	///
+	/// The unstable pipeline comes first, followed by stable releases from newest to oldest.
+	/// Stages we don't track are left out, as are pipelines without any stages
+	pub fn for_branches(&self, tracked: &[String]) -> Vec<Pipeline> {
+		let mut releases: Vec<_> = tracked
+			.iter()
+			.filter_map(|branch| branches::release(branch))
+			.collect();
+		releases.sort_unstable();
+		releases.dedup();
+
+		let unstable = Pipeline::new("unstable".to_string(), &self.unstable, tracked);
+		let stable = releases.into_iter().rev().map(|release| {
+			let release = version(release);
+			Pipeline::new(
+				release.clone(),
+				&self.release.for_release(&release),
+				tracked,
+			)
+		});
+
+		std::iter::once(unstable)
+			.chain(stable)
+			.filter(|pipeline| !pipeline.stages.is_empty())
+			.collect()
+	}
+
+	/// Get the `tracked` branches a change merged into `base` might be backported to
+	///
+	/// Changes to the unstable pipeline are backported into the `release-*` branches of stable
+	/// releases, and the rest of their pipelines get them from there. Nothing is backported out
+	/// of a stable release
+	pub fn backport_targets(&self, base: &str, tracked: &[String]) -> Vec<String> {
+		if self.unstable.position(base).is_none() {
+			return vec![];
+		}
+
+		tracked
+			.iter()
+			.filter(|branch| takes_backports(branch))
+			.cloned()
+			.collect()
+	}
+
+	/// Rank how early a change reaches a branch, starting from 0
+	///
+	/// Changes reach earlier stages of a pipeline first, so this tells which branch one was
+	/// merged into. Stable releases branch off from the unstable pipeline with all of its
+	/// history, so every stage of it comes before any of theirs. Branches that aren't part of a
+	/// pipeline come last
+	pub fn stage(&self, branch: &str) -> usize {
+		if let Some(index) = self.unstable.position(branch) {
+			return self.unstable.depth(index);
+		}
+
+		let layout = self.layout_of(branch);
+		layout.position(branch).map_or(usize::MAX, |index| {
+			self.unstable.stages.len() + layout.depth(index)
+		})
+	}
+
+	/// Get the `tracked` branches a change merged into `base` will move through, in order
+	///
+	/// Returns [`None`] if `base` isn't part of any pipeline
+	pub fn downstream(&self, base: &str, tracked: &[String]) -> Option<Vec<String>> {
+		let layout = self.layout_of(base);
+		let start = layout.position(base)?;
+
+		Some(
+			layout
+				.stages
+				.iter()
+				.enumerate()
+				.filter(|(index, stage)| {
+					leads_to(&layout.follows, start, *index) && tracked.contains(stage)
+				})
+				.map(|(_, stage)| stage.clone())
+				.collect(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{takes_backports, Pipelines, Progress};
+
+	fn strings(branches: &[&str]) -> Vec<String> {
+		branches.iter().map(ToString::to_string).collect()
+	}
+
+	fn tracked() -> Vec<String> {
+		strings(&[
+			"staging",
+			"master",
+			"nixpkgs-unstable",
+			"nixos-unstable-small",
+			"nixos-unstable",
+			"release-24.05",
+			"nixos-24.05",
+		])
+	}
+
+	#[test]
+	fn rejects_bad_layouts() {
+		assert!(Pipelines::parse("a > b; b > c", "d-{release}").is_ok());
+		assert!(Pipelines::parse("", "d-{release}").is_err());
+		assert!(Pipelines::parse("a > > b", "d-{release}").is_err());
+		assert!(Pipelines::parse("a > b > a", "d-{release}").is_err());
+		assert!(Pipelines::parse("a > b; c > b", "d-{release}").is_err());
+	}
+
+	#[test]
+	fn finds_downstream_branches() {
+		let pipelines = Pipelines::default();
+		let tracked = tracked();
+
+		assert_eq!(
+			pipelines.downstream("staging", &tracked),
+			Some(strings(&[
+				"staging",
+				"master",
+				"nixpkgs-unstable",
+				"nixos-unstable-small",
+				"nixos-unstable",
+			]))
+		);
+		// channels advancing in parallel don't lead to each other
+		assert_eq!(
+			pipelines.downstream("nixpkgs-unstable", &tracked),
+			Some(strings(&["nixpkgs-unstable"]))
+		);
+		// stages we don't track still lead somewhere
+		assert_eq!(
+			pipelines.downstream("staging-next-24.05", &tracked),
+			Some(strings(&["release-24.05", "nixos-24.05"]))
+		);
+		assert_eq!(pipelines.downstream("haskell-updates", &tracked), None);
+	}
+
+	#[test]
+	fn ranks_unstable_stages_first() {
+		let pipelines = Pipelines::default();
+
+		assert_eq!(pipelines.stage("staging"), 0);
+		assert_eq!(pipelines.stage("master"), 2);
+		assert_eq!(pipelines.stage("nixos-unstable"), 4);
+		assert!(pipelines.stage("nixos-unstable") < pipelines.stage("staging-24.05"));
+		assert!(pipelines.stage("release-24.05") < pipelines.stage("nixos-24.05"));
+		assert_eq!(pipelines.stage("haskell-updates"), usize::MAX);
+	}
+
+	#[test]
+	fn backports_go_to_release_branches() {
+		let pipelines = Pipelines::default();
+		let tracked = tracked();
+
+		assert!(takes_backports("release-24.05"));
+		assert!(!takes_backports("nixos-24.05"));
+		assert_eq!(
+			pipelines.backport_targets("master", &tracked),
+			strings(&["release-24.05"])
+		);
+		assert!(pipelines
+			.backport_targets("release-24.05", &tracked)
+			.is_empty());
+	}
+
+	#[test]
+	fn follows_progress_on_every_path() {
+		let pipelines = Pipelines::default().for_branches(&tracked());
+		let names: Vec<_> = pipelines.iter().map(|pipeline| &pipeline.name).collect();
+		assert_eq!(names, ["unstable", "24.05"]);
+		let unstable = &pipelines[0];
+
+		assert_eq!(
+			unstable.chains(),
+			[
+				vec!["staging", "master", "nixpkgs-unstable"],
+				vec!["master", "nixos-unstable-small", "nixos-unstable"]
+			]
+		);
+
+		assert_eq!(
+			unstable.progress(|_| false),
+			Progress {
+				furthest: vec![],
+				next: vec!["staging"],
+			}
+		);
+		assert_eq!(
+			unstable.progress(|stage| ["staging", "master"].contains(&stage)),
+			Progress {
+				furthest: vec!["master"],
+				next: vec!["nixpkgs-unstable", "nixos-unstable-small"],
+			}
+		);
+		// PRs can be merged straight into `master`
+		assert_eq!(
+			unstable.progress(|stage| {
+				["master", "nixpkgs-unstable", "nixos-unstable-small"].contains(&stage)
+			}),
+			Progress {
+				furthest: vec!["nixpkgs-unstable", "nixos-unstable-small"],
+				next: vec!["nixos-unstable"],
+			}
+		);
+	}
+
+	#[test]
+	fn skips_stages_we_dont_track() {
+		let tracked = strings(&["master", "nixpkgs-unstable", "nixos-unstable"]);
+		let pipelines = Pipelines::default().for_branches(&tracked);
+		let unstable = &pipelines[0];
+
+		assert_eq!(
+			unstable.chains(),
+			[
+				vec!["master", "nixpkgs-unstable"],
+				vec!["master", "nixos-unstable"]
+			]
+		);
+		assert_eq!(
+			unstable.progress(|stage| stage == "master").next,
+			["nixpkgs-unstable", "nixos-unstable"]
+		);
+	}
+}
@@ -0,0 +1,36 @@
+//! Which of our branches guilds and users want to see
+use crate::storage::Storage;
+
+use serenity::model::id::{GuildId, UserId};
+
+/// Who a branch preference belongs to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Owner {
+	/// Everyone using us in a guild
+	Guild(GuildId),
+	/// A single user, wherever they use us
+	User(UserId),
+}
+
+/// Find the branch patterns someone wants to see, if they've picked any
+///
+/// A guild's choice applies to everyone in it, and a user's own choice applies everywhere else
+///
+/// # Errors
+///
+/// Will return [`Err`] if the database can't be queried
+pub fn lookup(
+	storage: &Storage,
+	guild_id: Option<GuildId>,
+	user_id: UserId,
+) -> rusqlite::Result<Option<Vec<String>>> {
+	let handle = storage.lock();
+	let preferences = handle.branch_preferences();
+	if let Some(guild_id) = guild_id {
+		if let Some(patterns) = preferences.get(Owner::Guild(guild_id))? {
+			return Ok(Some(patterns));
+		}
+	}
+
+	preferences.get(Owner::User(user_id))
+}
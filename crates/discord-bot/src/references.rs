@@ -0,0 +1,248 @@
+//! Understanding the ways people refer to nixpkgs PRs
+use crate::commands::{REPO_NAME, REPO_OWNER};
+
+use std::fmt;
+
+/// Why we couldn't understand a reference to a PR
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+	/// Nothing was given
+	Empty,
+	/// The PR is in a repository we don't track
+	WrongRepository(String),
+	/// The PR number can't be right
+	InvalidNumber(String),
+	/// We have no idea what this is
+	Unrecognized(String),
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Empty => write!(f, "I need a PR to track!"),
+			Self::WrongRepository(repository) => write!(
+				f,
+				"I can only track PRs in {REPO_OWNER}/{REPO_NAME}, not `{repository}`"
+			),
+			Self::InvalidNumber(number) => write!(f, "`{number}` isn't a valid PR number"),
+			Self::Unrecognized(input) => write!(
+				f,
+				"I don't understand `{input}`. Try a PR number, `#123`, `{REPO_OWNER}/{REPO_NAME}#123`, or a link to the PR"
+			),
+		}
+	}
+}
+
+/// Check if `owner/name` is the repository we track
+fn is_our_repository(owner: &str, name: &str) -> bool {
+	owner.eq_ignore_ascii_case(REPO_OWNER) && name.eq_ignore_ascii_case(REPO_NAME)
+}
+
+/// Parse a PR number, which has to be positive
+fn parse_number(number: &str) -> Result<u64, ParseError> {
+	match number.parse() {
+		Ok(0) | Err(_) => Err(ParseError::InvalidNumber(number.to_string())),
+		Ok(number) => Ok(number),
+	}
+}
+
+/// Parse a link to a PR, like `https://github.com/NixOS/nixpkgs/pull/123/files`
+fn parse_url(input: &str) -> Option<Result<u64, ParseError>> {
+	let rest = input
+		.strip_prefix("https://")
+		.or_else(|| input.strip_prefix("http://"))
+		.unwrap_or(input);
+	let rest = rest.strip_prefix("www.").unwrap_or(rest);
+	let path = rest.strip_prefix("github.com/")?;
+	// anything after the number (`/files`, `#issuecomment-123`, `?w=1`) doesn't matter
+	let path = path.split(['?', '#']).next().unwrap_or_default();
+
+	let mut segments = path.split('/');
+	let (Some(owner), Some(name), Some(kind), Some(number)) = (
+		segments.next(),
+		segments.next(),
+		segments.next(),
+		segments.next(),
+	) else {
+		return Some(Err(ParseError::Unrecognized(input.to_string())));
+	};
+
+	if kind != "pull" {
+		return Some(Err(ParseError::Unrecognized(input.to_string())));
+	}
+	if !is_our_repository(owner, name) {
+		return Some(Err(ParseError::WrongRepository(format!("{owner}/{name}"))));
+	}
+
+	Some(parse_number(number))
+}
+
+/// Understand a reference to a nixpkgs PR
+///
+/// This accepts plain numbers, `#123`, `NixOS/nixpkgs#123`, and links to the PR on GitHub
+///
+/// # Errors
+///
+/// Will return [`Err`] if `input` isn't a reference to a nixpkgs PR, with a reason that can be
+/// shown to users
+pub fn parse_pull_request(input: &str) -> Result<u64, ParseError> {
+	let input = input.trim();
+	if input.is_empty() {
+		return Err(ParseError::Empty);
+	}
+
+	if let Some(result) = parse_url(input) {
+		return result;
+	}
+
+	match input.split_once('#') {
+		// `#123`
+		Some(("", number)) => parse_number(number),
+		// `NixOS/nixpkgs#123`
+		Some((repository, number)) => {
+			let Some((owner, name)) = repository.split_once('/') else {
+				return Err(ParseError::Unrecognized(input.to_string()));
+			};
+			if !is_our_repository(owner, name) {
+				return Err(ParseError::WrongRepository(repository.to_string()));
+			}
+
+			parse_number(number)
+		}
+		// `123`
+		None if input.starts_with(|c: char| c.is_ascii_digit() || c == '-') => parse_number(input),
+		None => Err(ParseError::Unrecognized(input.to_string())),
+	}
+}
+
+/// Find every nixpkgs PR referenced in some text, in the order they first appear
+///
+/// Links and `NixOS/nixpkgs#123` are always found. Bare `#123`s are easy to use for other
+/// things, so they're only found when `include_bare` is set
+pub fn find_pull_requests(text: &str, include_bare: bool) -> Vec<u64> {
+	let mut found = vec![];
+	// `<` and `>` aren't separators, so `<#123>` channel mentions stay out of the way
+	for word in text.split(|c: char| c.is_whitespace() || "()[]\"'`,".contains(c)) {
+		// links with embeds suppressed look like `<https://...>`
+		let word = word
+			.strip_prefix("<http")
+			.map_or(word, |_| &word[1..])
+			.trim_end_matches(['.', ':', ';', '!', '?', '>']);
+
+		let number = if let Some(start) = word.find("github.com/") {
+			parse_url(&word[start..]).and_then(Result::ok)
+		} else if word.contains('/') && word.contains('#') {
+			parse_pull_request(word).ok()
+		} else if include_bare && word.starts_with('#') {
+			parse_number(&word[1..]).ok()
+		} else {
+			None
+		};
+
+		if let Some(number) = number.filter(|number| !found.contains(number)) {
+			found.push(number);
+		}
+	}
+
+	found
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{find_pull_requests, parse_pull_request, ParseError};
+
+	#[test]
+	fn parses_numbers() {
+		assert_eq!(parse_pull_request("123"), Ok(123));
+		assert_eq!(parse_pull_request(" #123 "), Ok(123));
+		assert_eq!(parse_pull_request("NixOS/nixpkgs#123"), Ok(123));
+		assert_eq!(parse_pull_request("nixos/NIXPKGS#123"), Ok(123));
+	}
+
+	#[test]
+	fn parses_links() {
+		for link in [
+			"https://github.com/NixOS/nixpkgs/pull/123",
+			"http://www.github.com/NixOS/nixpkgs/pull/123",
+			"github.com/NixOS/nixpkgs/pull/123",
+			"https://github.com/NixOS/nixpkgs/pull/123/files",
+			"https://github.com/NixOS/nixpkgs/pull/123#issuecomment-456",
+			"https://github.com/NixOS/nixpkgs/pull/123?w=1",
+		] {
+			assert_eq!(parse_pull_request(link), Ok(123), "{link}");
+		}
+	}
+
+	#[test]
+	fn rejects_bad_numbers() {
+		for input in [
+			"#0",
+			"0",
+			"-5",
+			"#abc",
+			"NixOS/nixpkgs#",
+			"https://github.com/NixOS/nixpkgs/pull/0",
+		] {
+			assert!(
+				matches!(parse_pull_request(input), Err(ParseError::InvalidNumber(_))),
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn rejects_other_repositories() {
+		assert_eq!(
+			parse_pull_request("NixOS/nix#123"),
+			Err(ParseError::WrongRepository("NixOS/nix".to_string()))
+		);
+		assert_eq!(
+			parse_pull_request("https://github.com/NixOS/nix/pull/123"),
+			Err(ParseError::WrongRepository("NixOS/nix".to_string()))
+		);
+	}
+
+	#[test]
+	fn rejects_everything_else() {
+		assert_eq!(parse_pull_request("  "), Err(ParseError::Empty));
+		for input in [
+			"hello",
+			"nixpkgs#123",
+			"https://github.com/NixOS/nixpkgs/issues/123",
+			"https://github.com/NixOS/nixpkgs",
+		] {
+			assert!(
+				matches!(parse_pull_request(input), Err(ParseError::Unrecognized(_))),
+				"{input}"
+			);
+		}
+	}
+
+	#[test]
+	fn finds_links_in_text() {
+		let text = "Fixed in https://github.com/NixOS/nixpkgs/pull/1. Backported in \
+			<https://github.com/NixOS/nixpkgs/pull/2>, see also \
+			(https://github.com/NixOS/nixpkgs/pull/3/files) and NixOS/nixpkgs#4!";
+		assert_eq!(find_pull_requests(text, false), [1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn finds_each_pull_request_once() {
+		let text = "NixOS/nixpkgs#2 https://github.com/NixOS/nixpkgs/pull/1 NixOS/nixpkgs#2";
+		assert_eq!(find_pull_requests(text, false), [2, 1]);
+	}
+
+	#[test]
+	fn skips_what_isnt_ours() {
+		let text = "https://github.com/NixOS/nix/pull/1 NixOS/nix#2 \
+			https://github.com/NixOS/nixpkgs/issues/3 #0 NixOS/nixpkgs#-5";
+		assert!(find_pull_requests(text, true).is_empty());
+	}
+
+	#[test]
+	fn finds_bare_numbers_only_when_asked() {
+		let text = "see #123, but not <#456>";
+		assert!(find_pull_requests(text, false).is_empty());
+		assert_eq!(find_pull_requests(text, true), [123]);
+	}
+}
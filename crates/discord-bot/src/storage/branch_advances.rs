@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection};
+
+/// A branch moving from one commit to another
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Advance {
+	/// Where the branch pointed before
+	pub old_sha: String,
+	/// Where the branch pointed after
+	pub new_sha: String,
+	/// When we saw it happen, as a Unix timestamp
+	pub seen_at: i64,
+}
+
+/// When we saw each branch move forward
+pub struct BranchAdvances<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl BranchAdvances<'_> {
+	/// Remember that a branch just advanced from one commit to another
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the advance can't be saved
+	pub fn record(&self, branch: &str, old_sha: &str, new_sha: &str) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"INSERT OR REPLACE INTO branch_advances (branch, commit_sha, seen_at)
+			VALUES (?1, ?2, unixepoch())",
+			params![branch, new_sha],
+		)?;
+		self.connection.execute(
+			"INSERT INTO branch_history (branch, old_sha, new_sha, seen_at)
+			VALUES (?1, ?2, ?3, unixepoch())",
+			params![branch, old_sha, new_sha],
+		)?;
+
+		Ok(())
+	}
+
+	/// Get when we last saw each branch advance, as Unix timestamps
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn all(&self) -> rusqlite::Result<HashMap<String, i64>> {
+		let mut statement = self
+			.connection
+			.prepare("SELECT branch, seen_at FROM branch_advances")?;
+		let advances = statement
+			.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+			.collect::<rusqlite::Result<_>>()?;
+
+		Ok(advances)
+	}
+
+	/// Get every advance we've seen of a branch, oldest first
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn history(&self, branch: &str) -> rusqlite::Result<Vec<Advance>> {
+		let mut statement = self.connection.prepare(
+			"SELECT old_sha, new_sha, seen_at FROM branch_history
+			WHERE branch = ?1
+			ORDER BY seen_at, rowid",
+		)?;
+		let advances = statement
+			.query_map(params![branch], |row| {
+				Ok(Advance {
+					old_sha: row.get(0)?,
+					new_sha: row.get(1)?,
+					seen_at: row.get(2)?,
+				})
+			})?
+			.collect::<rusqlite::Result<_>>()?;
+
+		Ok(advances)
+	}
+}
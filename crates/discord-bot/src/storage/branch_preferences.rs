@@ -0,0 +1,73 @@
+use crate::preferences::Owner;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Branch patterns guilds and users have picked
+pub struct BranchPreferences<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl Owner {
+	fn kind(&self) -> &'static str {
+		match self {
+			Self::Guild(_) => "guild",
+			Self::User(_) => "user",
+		}
+	}
+
+	fn id(&self) -> u64 {
+		match self {
+			Self::Guild(guild_id) => guild_id.get(),
+			Self::User(user_id) => user_id.get(),
+		}
+	}
+}
+
+impl BranchPreferences<'_> {
+	/// Get the branch patterns an [`Owner`] has picked
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn get(&self, owner: Owner) -> rusqlite::Result<Option<Vec<String>>> {
+		let patterns: Option<String> = self
+			.connection
+			.query_row(
+				"SELECT patterns FROM branch_preferences WHERE owner_kind = ?1 AND owner_id = ?2",
+				params![owner.kind(), owner.id()],
+				|row| row.get(0),
+			)
+			.optional()?;
+
+		Ok(patterns.map(|patterns| patterns.split(',').map(ToString::to_string).collect()))
+	}
+
+	/// Pick the branch patterns for an [`Owner`], replacing any they had
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the preference can't be saved
+	pub fn set(&self, owner: Owner, patterns: &[String]) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"INSERT OR REPLACE INTO branch_preferences (owner_kind, owner_id, patterns)
+			VALUES (?1, ?2, ?3)",
+			params![owner.kind(), owner.id(), patterns.join(",")],
+		)?;
+
+		Ok(())
+	}
+
+	/// Go back to showing an [`Owner`] every branch, returning whether they had picked any
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the preference can't be deleted
+	pub fn remove(&self, owner: Owner) -> rusqlite::Result<bool> {
+		let removed = self.connection.execute(
+			"DELETE FROM branch_preferences WHERE owner_kind = ?1 AND owner_id = ?2",
+			params![owner.kind(), owner.id()],
+		)?;
+
+		Ok(removed > 0)
+	}
+}
@@ -0,0 +1,190 @@
+use log::{debug, info};
+use rusqlite::{Connection, Transaction};
+
+/// Schema changes, applied in order. Never edit or remove one that has shipped!
+const MIGRATIONS: &[&str] = &[
+	// 1: subscriptions & PR cache
+	"
+	CREATE TABLE subscriptions (
+		pull_request INTEGER NOT NULL,
+		title TEXT NOT NULL,
+		html_url TEXT NOT NULL,
+		commit_sha TEXT NOT NULL,
+		target_kind TEXT NOT NULL,
+		target_id INTEGER NOT NULL,
+		PRIMARY KEY (pull_request, target_kind, target_id)
+	);
+
+	CREATE TABLE subscription_branches (
+		pull_request INTEGER NOT NULL,
+		target_kind TEXT NOT NULL,
+		target_id INTEGER NOT NULL,
+		branch TEXT NOT NULL,
+		PRIMARY KEY (pull_request, target_kind, target_id, branch),
+		FOREIGN KEY (pull_request, target_kind, target_id)
+			REFERENCES subscriptions (pull_request, target_kind, target_id)
+			ON DELETE CASCADE
+	);
+
+	CREATE TABLE pull_requests (
+		number INTEGER PRIMARY KEY,
+		html_url TEXT NOT NULL,
+		title TEXT NOT NULL,
+		merged INTEGER NOT NULL,
+		merged_at TEXT,
+		merge_commit_sha TEXT
+	);
+	",
+	// 2: base branches, so we know which branches matter for a PR. PRs cached before this
+	// are fetched again
+	"
+	ALTER TABLE pull_requests ADD COLUMN base_ref TEXT;
+	ALTER TABLE subscriptions ADD COLUMN base_ref TEXT;
+	",
+	// 3: channels we watch for PRs, and messages we've already responded to in them
+	"
+	CREATE TABLE watched_channels (
+		channel_id INTEGER PRIMARY KEY,
+		guild_id INTEGER NOT NULL,
+		mode TEXT NOT NULL
+	);
+
+	CREATE INDEX watched_channels_guild ON watched_channels (guild_id);
+
+	CREATE TABLE seen_messages (
+		message_id INTEGER PRIMARY KEY,
+		seen_at INTEGER NOT NULL
+	);
+	",
+	// 4: branches guilds and users want to see
+	"
+	CREATE TABLE branch_preferences (
+		owner_kind TEXT NOT NULL,
+		owner_id INTEGER NOT NULL,
+		patterns TEXT NOT NULL,
+		PRIMARY KEY (owner_kind, owner_id)
+	);
+	",
+	// 5: when we last saw each branch advance
+	"
+	CREATE TABLE branch_advances (
+		branch TEXT PRIMARY KEY,
+		commit_sha TEXT NOT NULL,
+		seen_at INTEGER NOT NULL
+	);
+	",
+	// 6: commits of merged PRs, for finding their backports
+	"
+	ALTER TABLE pull_requests ADD COLUMN commit_shas TEXT;
+	",
+	// 7: every advance of every branch, so we know when channels picked up commits
+	"
+	CREATE TABLE branch_history (
+		branch TEXT NOT NULL,
+		old_sha TEXT NOT NULL,
+		new_sha TEXT NOT NULL,
+		seen_at INTEGER NOT NULL
+	);
+
+	CREATE INDEX branch_history_branch ON branch_history (branch, seen_at);
+	",
+];
+
+/// Bring the database's schema up to date
+///
+/// # Errors
+///
+/// Will return [`Err`] if the current version can't be read or a migration fails
+pub fn run(connection: &mut Connection) -> rusqlite::Result<()> {
+	let current: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+	debug!("Database is at schema version {current}");
+
+	for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+		let version = index + 1;
+		info!("Migrating database to schema version {version}");
+
+		let transaction = connection.transaction()?;
+		apply(&transaction, migration, version)?;
+		transaction.commit()?;
+	}
+
+	Ok(())
+}
+
+fn apply(transaction: &Transaction, migration: &str, version: usize) -> rusqlite::Result<()> {
+	transaction.execute_batch(migration)?;
+	// `PRAGMA` doesn't support bound parameters
+	transaction.execute_batch(&format!("PRAGMA user_version = {version}"))?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{apply, run, MIGRATIONS};
+
+	use rusqlite::Connection;
+
+	fn version(connection: &Connection) -> usize {
+		connection
+			.query_row("PRAGMA user_version", [], |row| row.get(0))
+			.unwrap()
+	}
+
+	#[test]
+	fn brings_new_databases_up_to_date() {
+		let mut connection = Connection::open_in_memory().unwrap();
+		run(&mut connection).unwrap();
+		assert_eq!(version(&connection), MIGRATIONS.len());
+
+		let tables: Vec<String> = connection
+			.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+			.unwrap()
+			.query_map([], |row| row.get(0))
+			.unwrap()
+			.collect::<rusqlite::Result<_>>()
+			.unwrap();
+		assert_eq!(
+			tables,
+			[
+				"branch_advances",
+				"branch_history",
+				"branch_preferences",
+				"pull_requests",
+				"seen_messages",
+				"subscription_branches",
+				"subscriptions",
+				"watched_channels",
+			]
+		);
+
+		// nothing is left to apply the second time around
+		run(&mut connection).unwrap();
+		assert_eq!(version(&connection), MIGRATIONS.len());
+	}
+
+	#[test]
+	fn picks_up_where_it_left_off() {
+		let mut connection = Connection::open_in_memory().unwrap();
+		let transaction = connection.transaction().unwrap();
+		apply(&transaction, MIGRATIONS[0], 1).unwrap();
+		transaction.commit().unwrap();
+		connection
+			.execute(
+				"INSERT INTO pull_requests (number, html_url, title, merged) VALUES (1, '', '', 0)",
+				[],
+			)
+			.unwrap();
+
+		run(&mut connection).unwrap();
+		assert_eq!(version(&connection), MIGRATIONS.len());
+		let base_ref: Option<String> = connection
+			.query_row(
+				"SELECT base_ref FROM pull_requests WHERE number = 1",
+				[],
+				|row| row.get(0),
+			)
+			.unwrap();
+		assert_eq!(base_ref, None);
+	}
+}
@@ -0,0 +1,112 @@
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use eyre::Result;
+use log::trace;
+use rusqlite::Connection;
+
+mod branch_advances;
+mod branch_preferences;
+mod migrations;
+mod pull_requests;
+mod seen_messages;
+mod subscriptions;
+mod watched_channels;
+
+pub use branch_advances::BranchAdvances;
+pub use branch_preferences::BranchPreferences;
+pub use pull_requests::PullRequests;
+pub use seen_messages::SeenMessages;
+pub use subscriptions::Subscriptions;
+pub use watched_channels::WatchedChannels;
+
+/// Persistent state for the bot, backed by `SQLite`
+#[derive(Debug)]
+pub struct Storage {
+	connection: Mutex<Connection>,
+}
+
+impl Storage {
+	/// Open (or create) the database at `path` and bring it up to date
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be opened or migrated
+	pub fn open(path: &Path) -> Result<Self> {
+		trace!("Opening database at {}", path.display());
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+
+		let mut connection = Connection::open(path)?;
+		connection.pragma_update(None, "foreign_keys", true)?;
+		connection.pragma_update(None, "journal_mode", "WAL")?;
+		migrations::run(&mut connection)?;
+
+		Ok(Self {
+			connection: Mutex::new(connection),
+		})
+	}
+
+	/// Lock the database for a series of operations
+	///
+	/// A panic while someone else held the lock doesn't keep us out. Every statement is applied
+	/// atomically by `SQLite`, so there's no half-finished state to protect
+	pub fn lock(&self) -> Handle<'_> {
+		Handle {
+			connection: self
+				.connection
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner),
+		}
+	}
+}
+
+/// Exclusive access to [`Storage`]
+pub struct Handle<'a> {
+	connection: MutexGuard<'a, Connection>,
+}
+
+impl Handle<'_> {
+	/// Access persisted subscriptions
+	pub fn subscriptions(&self) -> Subscriptions<'_> {
+		Subscriptions {
+			connection: &self.connection,
+		}
+	}
+
+	/// Access cached PRs
+	pub fn pull_requests(&self) -> PullRequests<'_> {
+		PullRequests {
+			connection: &self.connection,
+		}
+	}
+
+	/// Access channels we watch for PRs
+	pub fn watched_channels(&self) -> WatchedChannels<'_> {
+		WatchedChannels {
+			connection: &self.connection,
+		}
+	}
+
+	/// Access the branches guilds and users want to see
+	pub fn branch_preferences(&self) -> BranchPreferences<'_> {
+		BranchPreferences {
+			connection: &self.connection,
+		}
+	}
+
+	/// Access when we last saw each branch advance
+	pub fn branch_advances(&self) -> BranchAdvances<'_> {
+		BranchAdvances {
+			connection: &self.connection,
+		}
+	}
+
+	/// Access messages we've already responded to
+	pub fn seen_messages(&self) -> SeenMessages<'_> {
+		SeenMessages {
+			connection: &self.connection,
+		}
+	}
+}
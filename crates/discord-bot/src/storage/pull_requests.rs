@@ -0,0 +1,104 @@
+use crate::http::{Base, PullRequest};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Cache of PRs we've looked up before
+///
+/// Merged PRs can't really change, so we only ever need to ask GitHub about them once
+pub struct PullRequests<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl PullRequests<'_> {
+	/// Find a cached [`PullRequest`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn get(&self, number: u64) -> rusqlite::Result<Option<PullRequest>> {
+		self.connection
+			.query_row(
+				"SELECT html_url, number, title, merged, merged_at, merge_commit_sha, base_ref
+				FROM pull_requests WHERE number = ?1 AND base_ref IS NOT NULL",
+				[number],
+				|row| {
+					Ok(PullRequest {
+						html_url: row.get(0)?,
+						number: row.get(1)?,
+						title: row.get(2)?,
+						merged: row.get(3)?,
+						merged_at: row.get(4)?,
+						merge_commit_sha: row.get(5)?,
+						base: Base {
+							branch: row.get(6)?,
+						},
+					})
+				},
+			)
+			.optional()
+	}
+
+	/// Cache a [`PullRequest`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the PR can't be saved
+	pub fn insert(&self, pull_request: &PullRequest) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"INSERT OR REPLACE INTO pull_requests
+			(number, html_url, title, merged, merged_at, merge_commit_sha, base_ref)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+			params![
+				pull_request.number,
+				pull_request.html_url,
+				pull_request.title,
+				pull_request.merged,
+				pull_request.merged_at,
+				pull_request.merge_commit_sha,
+				pull_request.base.branch,
+			],
+		)?;
+
+		Ok(())
+	}
+
+	/// Get the SHAs of a cached PR's commits, if we've saved them
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn commits(&self, number: u64) -> rusqlite::Result<Option<Vec<String>>> {
+		// PRs we haven't cached and PRs without saved commits look the same to us
+		let commit_shas: Option<String> = self
+			.connection
+			.query_row(
+				"SELECT commit_shas FROM pull_requests WHERE number = ?1",
+				[number],
+				|row| row.get::<_, Option<String>>(0),
+			)
+			.optional()?
+			.flatten();
+
+		Ok(commit_shas.map(|commit_shas| {
+			commit_shas
+				.split(',')
+				.filter(|sha| !sha.is_empty())
+				.map(ToString::to_string)
+				.collect()
+		}))
+	}
+
+	/// Save the SHAs of a cached PR's commits. Nothing is saved for PRs that aren't cached
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the commits can't be saved
+	pub fn insert_commits(&self, number: u64, commit_shas: &[String]) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"UPDATE pull_requests SET commit_shas = ?2 WHERE number = ?1",
+			params![number, commit_shas.join(",")],
+		)?;
+
+		Ok(())
+	}
+}
@@ -0,0 +1,31 @@
+use rusqlite::Connection;
+use serenity::model::id::MessageId;
+
+/// How long we remember messages for. Repeated events show up well within this
+const TTL_SECS: u64 = 60 * 60 * 24; // 1 day
+
+/// Messages we've already responded to
+pub struct SeenMessages<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl SeenMessages<'_> {
+	/// Remember a message, returning whether it's the first time we've seen it
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the message can't be saved
+	pub fn insert(&self, message_id: MessageId) -> rusqlite::Result<bool> {
+		// forget about anything old enough that it won't come back
+		self.connection.execute(
+			"DELETE FROM seen_messages WHERE seen_at < unixepoch() - ?1",
+			[TTL_SECS],
+		)?;
+		let inserted = self.connection.execute(
+			"INSERT OR IGNORE INTO seen_messages (message_id, seen_at) VALUES (?1, unixepoch())",
+			[message_id.get()],
+		)?;
+
+		Ok(inserted > 0)
+	}
+}
@@ -0,0 +1,164 @@
+use crate::subscriptions::{Subscription, Target};
+
+use std::collections::HashSet;
+
+use rusqlite::{params, Connection, Row};
+use serenity::model::id::{ChannelId, UserId};
+
+/// Persisted [`Subscription`]s
+pub struct Subscriptions<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl Target {
+	fn kind(&self) -> &'static str {
+		match self {
+			Self::User(_) => "user",
+			Self::Channel(_) => "channel",
+		}
+	}
+
+	fn id(&self) -> u64 {
+		match self {
+			Self::User(user_id) => user_id.get(),
+			Self::Channel(channel_id) => channel_id.get(),
+		}
+	}
+
+	fn from_row(kind: &str, id: u64) -> Option<Self> {
+		match kind {
+			"user" => Some(Self::User(UserId::new(id))),
+			"channel" => Some(Self::Channel(ChannelId::new(id))),
+			_ => None,
+		}
+	}
+}
+
+impl Subscriptions<'_> {
+	/// Add a new [`Subscription`], replacing any existing one for the same PR and target
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the subscription can't be saved
+	pub fn insert(&self, subscription: &Subscription) -> rusqlite::Result<()> {
+		let target = subscription.target;
+		self.connection.execute(
+			"INSERT OR REPLACE INTO subscriptions
+			(pull_request, title, html_url, commit_sha, target_kind, target_id, base_ref)
+			VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+			params![
+				subscription.pull_request,
+				subscription.title,
+				subscription.html_url,
+				subscription.commit_sha,
+				target.kind(),
+				target.id(),
+				subscription.base_ref,
+			],
+		)?;
+
+		self.mark_reached(
+			subscription.pull_request,
+			target,
+			&subscription.reached_branches,
+		)
+	}
+
+	/// Get every pending [`Subscription`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn all(&self) -> rusqlite::Result<Vec<Subscription>> {
+		let mut statement = self.connection.prepare(
+			"SELECT pull_request, title, html_url, commit_sha, target_kind, target_id, base_ref
+			FROM subscriptions",
+		)?;
+		let rows = statement.query_map([], Self::from_row)?;
+
+		let mut subscriptions = vec![];
+		for row in rows {
+			let Some(mut subscription) = row? else {
+				continue;
+			};
+			subscription.reached_branches =
+				self.reached_branches(subscription.pull_request, subscription.target)?;
+			subscriptions.push(subscription);
+		}
+
+		Ok(subscriptions)
+	}
+
+	/// Remember that we've notified a [`Target`] about some branches
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches can't be saved
+	pub fn mark_reached<'b>(
+		&self,
+		pull_request: u64,
+		target: Target,
+		branches: impl IntoIterator<Item = &'b String>,
+	) -> rusqlite::Result<()> {
+		let mut statement = self.connection.prepare(
+			"INSERT OR IGNORE INTO subscription_branches
+			(pull_request, target_kind, target_id, branch)
+			VALUES (?1, ?2, ?3, ?4)",
+		)?;
+		for branch in branches {
+			statement.execute(params![pull_request, target.kind(), target.id(), branch])?;
+		}
+
+		Ok(())
+	}
+
+	/// Remove a [`Subscription`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the subscription can't be deleted
+	pub fn remove(&self, pull_request: u64, target: Target) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"DELETE FROM subscriptions
+			WHERE pull_request = ?1 AND target_kind = ?2 AND target_id = ?3",
+			params![pull_request, target.kind(), target.id()],
+		)?;
+
+		Ok(())
+	}
+
+	fn reached_branches(
+		&self,
+		pull_request: u64,
+		target: Target,
+	) -> rusqlite::Result<HashSet<String>> {
+		let mut statement = self.connection.prepare(
+			"SELECT branch FROM subscription_branches
+			WHERE pull_request = ?1 AND target_kind = ?2 AND target_id = ?3",
+		)?;
+		let branches = statement
+			.query_map(params![pull_request, target.kind(), target.id()], |row| {
+				row.get(0)
+			})?
+			.collect::<rusqlite::Result<_>>()?;
+
+		Ok(branches)
+	}
+
+	fn from_row(row: &Row) -> rusqlite::Result<Option<Subscription>> {
+		let target_kind: String = row.get(4)?;
+		let Some(target) = Target::from_row(&target_kind, row.get(5)?) else {
+			return Ok(None);
+		};
+
+		Ok(Some(Subscription {
+			pull_request: row.get(0)?,
+			title: row.get(1)?,
+			html_url: row.get(2)?,
+			commit_sha: row.get(3)?,
+			target,
+			base_ref: row.get(6)?,
+			reached_branches: HashSet::new(),
+		}))
+	}
+}
@@ -0,0 +1,87 @@
+use crate::links::Mode;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::model::id::{ChannelId, GuildId};
+
+/// Channels where we look for PRs in every message
+pub struct WatchedChannels<'a> {
+	pub(super) connection: &'a Connection,
+}
+
+impl WatchedChannels<'_> {
+	/// Start watching a channel, replacing how we respond in it if we already were
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the channel can't be saved
+	pub fn insert(
+		&self,
+		guild_id: GuildId,
+		channel_id: ChannelId,
+		mode: Mode,
+	) -> rusqlite::Result<()> {
+		self.connection.execute(
+			"INSERT OR REPLACE INTO watched_channels (channel_id, guild_id, mode)
+			VALUES (?1, ?2, ?3)",
+			params![channel_id.get(), guild_id.get(), mode.as_str()],
+		)?;
+
+		Ok(())
+	}
+
+	/// Stop watching a channel, returning whether we were
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the channel can't be deleted
+	pub fn remove(&self, channel_id: ChannelId) -> rusqlite::Result<bool> {
+		let removed = self.connection.execute(
+			"DELETE FROM watched_channels WHERE channel_id = ?1",
+			[channel_id.get()],
+		)?;
+
+		Ok(removed > 0)
+	}
+
+	/// Find out how we respond in a channel, if we watch it
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn get(&self, channel_id: ChannelId) -> rusqlite::Result<Option<Mode>> {
+		let mode: Option<String> = self
+			.connection
+			.query_row(
+				"SELECT mode FROM watched_channels WHERE channel_id = ?1",
+				[channel_id.get()],
+				|row| row.get(0),
+			)
+			.optional()?;
+
+		Ok(mode.as_deref().and_then(Mode::parse))
+	}
+
+	/// Get every channel we watch in a guild
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the database can't be queried
+	pub fn in_guild(&self, guild_id: GuildId) -> rusqlite::Result<Vec<(ChannelId, Mode)>> {
+		let mut statement = self.connection.prepare(
+			"SELECT channel_id, mode FROM watched_channels WHERE guild_id = ?1 ORDER BY channel_id",
+		)?;
+		let rows = statement.query_map([guild_id.get()], |row| {
+			let mode: String = row.get(1)?;
+			Ok((ChannelId::new(row.get(0)?), Mode::parse(&mode)))
+		})?;
+
+		let mut channels = vec![];
+		for row in rows {
+			if let (channel_id, Some(mode)) = row? {
+				channels.push((channel_id, mode));
+			}
+		}
+
+		Ok(channels)
+	}
+}
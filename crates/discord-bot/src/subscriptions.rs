@@ -0,0 +1,195 @@
+use crate::{config::Config, storage::Storage};
+
+use std::collections::HashSet;
+
+use eyre::Result;
+use git_tracker::Oid;
+use log::{debug, error, trace, warn};
+use serenity::builder::{CreateEmbed, CreateMessage};
+use serenity::http::Http;
+use serenity::model::{
+	colour::Colour,
+	id::{ChannelId, UserId},
+};
+
+/// Where we should send notifications for a [`Subscription`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+	/// Direct message a user
+	User(UserId),
+	/// Send a message in a channel
+	Channel(ChannelId),
+}
+
+/// A pending request to be notified when a PR reaches our tracked branches
+#[derive(Clone, Debug)]
+pub struct Subscription {
+	/// Number of the PR
+	pub pull_request: u64,
+	/// Title of the PR
+	pub title: String,
+	/// Link to the PR
+	pub html_url: String,
+	/// Commit the PR was merged in
+	pub commit_sha: String,
+	/// Who to notify
+	pub target: Target,
+	/// Branch the PR was merged into. Subscriptions from before we kept track of this don't
+	/// have one, and wait on every branch
+	pub base_ref: Option<String>,
+	/// Branches we've already sent a notification for (or that already had the commit)
+	pub reached_branches: HashSet<String>,
+}
+
+/// Get the branches a PR merged into `base` will reach that we track
+///
+/// Subscriptions from before we kept track of bases wait on every branch. Otherwise, this is
+/// empty if `base` isn't part of a pipeline, or we don't track anything after it
+pub fn relevant_branches(config: &Config, base: Option<&str>) -> Vec<String> {
+	let branches = config.nixpkgs_branches();
+	match base {
+		Some(base) => config
+			.pipelines()
+			.downstream(base, &branches)
+			.unwrap_or_default(),
+		None => branches,
+	}
+}
+
+/// Check pending [`Subscription`]s against the tracked repository and notify
+/// everyone whose PR has reached a new branch
+///
+/// # Errors
+///
+/// Will return [`Err`] if the repository or database can't be checked
+pub async fn notify(http: &Http, config: &Config, storage: &Storage) -> Result<()> {
+	let pending = storage.lock().subscriptions().all()?;
+	trace!("Checking {} pending subscriptions", pending.len());
+	if pending.is_empty() {
+		return Ok(());
+	}
+
+	let repository = config.repository();
+	let branches = config.nixpkgs_branches();
+	// check everything at once rather than walking the repository for every PR
+	let reachability = repository
+		.branches_contain_shas(
+			branches.clone(),
+			pending
+				.iter()
+				.map(|subscription| subscription.commit_sha.clone())
+				.collect(),
+		)
+		.await?;
+
+	for subscription in pending {
+		let relevant = relevant_branches(config, subscription.base_ref.as_deref());
+		// we won't ever have anything to say, so let them know instead of waiting forever
+		if relevant.is_empty() {
+			debug!(
+				"PR #{} can't reach any branch we track, dropping subscription of {:?}",
+				subscription.pull_request, subscription.target
+			);
+			if let Err(why) = send(http, &subscription, untracked_embed(&subscription)).await {
+				error!(
+					"Couldn't notify {:?} about PR #{}!\n{why:?}",
+					subscription.target, subscription.pull_request
+				);
+			}
+			storage
+				.lock()
+				.subscriptions()
+				.remove(subscription.pull_request, subscription.target)?;
+			continue;
+		}
+
+		let Ok(commit) = Oid::from_str(&subscription.commit_sha) else {
+			warn!(
+				"Skipping subscription to PR #{} with invalid commit {}",
+				subscription.pull_request, subscription.commit_sha
+			);
+			continue;
+		};
+		let newly_reached: Vec<_> = reachability
+			.branches_containing(commit)
+			.filter(|name| relevant.contains(*name))
+			.filter(|name| !subscription.reached_branches.contains(*name))
+			.cloned()
+			.collect();
+
+		for branch in &newly_reached {
+			debug!(
+				"PR #{} reached {branch}, notifying {:?}",
+				subscription.pull_request, subscription.target
+			);
+
+			if let Err(why) = send(http, &subscription, reached_embed(&subscription, branch)).await
+			{
+				error!(
+					"Couldn't notify {:?} about PR #{}!\n{why:?}",
+					subscription.target, subscription.pull_request
+				);
+			}
+		}
+
+		let handle = storage.lock();
+		let subscriptions = handle.subscriptions();
+		let is_complete = relevant.iter().all(|branch| {
+			subscription.reached_branches.contains(branch) || newly_reached.contains(branch)
+		});
+		if is_complete {
+			subscriptions.remove(subscription.pull_request, subscription.target)?;
+		} else {
+			subscriptions.mark_reached(
+				subscription.pull_request,
+				subscription.target,
+				&newly_reached,
+			)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Announce that a PR reached a branch
+fn reached_embed(subscription: &Subscription, branch: &str) -> CreateEmbed {
+	CreateEmbed::new()
+		.title(format!(
+			"Nixpkgs PR #{} reached {branch}",
+			subscription.pull_request
+		))
+		.url(&subscription.html_url)
+		.description(&subscription.title)
+		.field(branch, "✅", true)
+		.color(Colour::DARK_GREEN)
+}
+
+/// Announce that we've stopped waiting on a PR, as it can't reach anything we track anymore
+fn untracked_embed(subscription: &Subscription) -> CreateEmbed {
+	CreateEmbed::new()
+		.title(format!(
+			"No more updates for Nixpkgs PR #{}",
+			subscription.pull_request
+		))
+		.url(&subscription.html_url)
+		.description(format!(
+			"{}\n\nI don't track any of the branches it's still headed for anymore",
+			subscription.title
+		))
+		.color(Colour::ORANGE)
+}
+
+async fn send(http: &Http, subscription: &Subscription, embed: CreateEmbed) -> Result<()> {
+	let message = CreateMessage::new().embed(embed);
+
+	match subscription.target {
+		Target::User(user_id) => {
+			user_id.direct_message(http, message).await?;
+		}
+		Target::Channel(channel_id) => {
+			channel_id.send_message(http, message).await?;
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,288 @@
+//! An async-friendly wrapper around [`TrackedRepository`]
+use crate::{
+	Backport, Error, FetchReport, Landing, Merge, MergedPullRequest, Oid, Problem, Reachability,
+	Tag, Tip, TrackedRepository,
+};
+
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+
+/// Runs [`TrackedRepository`] operations on Tokio's blocking thread pool
+///
+/// libgit2 is entirely synchronous, and walking the history of a large repository can take a
+/// while. This keeps that work off of async executors, while only letting a limited amount of it
+/// run at once
+#[derive(Clone, Debug)]
+pub struct AsyncTrackedRepository {
+	repository: Arc<TrackedRepository>,
+	permits: Arc<Semaphore>,
+}
+
+impl AsyncTrackedRepository {
+	/// Wrap a [`TrackedRepository`], allowing up to `max_concurrency` operations at once
+	#[must_use]
+	pub fn new(repository: TrackedRepository, max_concurrency: usize) -> Self {
+		Self {
+			repository: Arc::new(repository),
+			permits: Arc::new(Semaphore::new(max_concurrency.max(1))),
+		}
+	}
+
+	/// Get the underlying [`TrackedRepository`]
+	///
+	/// Don't use this for anything slow from an async context!
+	#[must_use]
+	pub fn blocking(&self) -> &TrackedRepository {
+		&self.repository
+	}
+
+	/// Run a function with the [`TrackedRepository`] on the blocking thread pool
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if `f` fails or panics
+	///
+	/// # Panics
+	///
+	/// Will [`panic!`] if our semaphore is closed (it never is)
+	pub async fn run<F, T>(&self, f: F) -> Result<T, Error>
+	where
+		F: FnOnce(&TrackedRepository) -> Result<T, Error> + Send + 'static,
+		T: Send + 'static,
+	{
+		let permit = self
+			.permits
+			.clone()
+			.acquire_owned()
+			.await
+			.expect("Repository semaphore should never be closed!");
+		let repository = self.repository.clone();
+
+		tokio::task::spawn_blocking(move || {
+			let _permit = permit;
+			f(&repository)
+		})
+		.await?
+	}
+
+	/// See [`TrackedRepository::clone_repository`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be cloned
+	pub async fn clone_repository(&self) -> Result<FetchReport, Error> {
+		self.run(|repository| repository.clone_repository(None))
+			.await
+	}
+
+	/// See [`TrackedRepository::fetch`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be fetched
+	pub async fn fetch(&self) -> Result<FetchReport, Error> {
+		self.run(|repository| repository.fetch(None)).await
+	}
+
+	/// See [`TrackedRepository::verify`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be checked
+	pub async fn verify(&self) -> Result<Vec<Problem>, Error> {
+		self.run(TrackedRepository::verify).await
+	}
+
+	/// See [`TrackedRepository::repair`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be checked or repaired
+	pub async fn repair(&self) -> Result<Vec<Problem>, Error> {
+		self.run(TrackedRepository::repair).await
+	}
+
+	/// Check if the repository can be opened
+	pub async fn exists(&self) -> bool {
+		self.run(|repository| repository.open().map(|_| ()))
+			.await
+			.is_ok()
+	}
+
+	/// See [`TrackedRepository::tracked_branches`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be listed
+	pub async fn tracked_branches(&self) -> Result<Vec<String>, Error> {
+		self.run(TrackedRepository::tracked_branches).await
+	}
+
+	/// See [`TrackedRepository::branch_tips`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be read
+	pub async fn branch_tips(&self, branch_names: Vec<String>) -> Result<Vec<Tip>, Error> {
+		self.run(move |repository| repository.branch_tips(&branch_names))
+			.await
+	}
+
+	/// See [`TrackedRepository::update_pull_request_index`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the index cannot be updated
+	pub async fn update_pull_request_index<F>(&self, stage: F) -> Result<usize, Error>
+	where
+		F: Fn(&str) -> usize + Send + 'static,
+	{
+		self.run(move |repository| repository.update_pull_request_index(stage))
+			.await
+	}
+
+	/// See [`TrackedRepository::find_merged_pull_request`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the index cannot be searched
+	pub async fn find_merged_pull_request(
+		&self,
+		number: u64,
+	) -> Result<Option<MergedPullRequest>, Error> {
+		self.run(move |repository| repository.find_merged_pull_request(number))
+			.await
+	}
+
+	/// See [`TrackedRepository::resolve_commit`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the SHA is ambiguous or the repository cannot be searched
+	pub async fn resolve_commit(&self, sha: String) -> Result<Option<Oid>, Error> {
+		self.run(move |repository| repository.resolve_commit(&sha))
+			.await
+	}
+
+	/// See [`TrackedRepository::find_merge`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be checked
+	pub async fn find_merge(
+		&self,
+		branch_names: Vec<String>,
+		commit_sha: String,
+	) -> Result<Option<Merge>, Error> {
+		self.run(move |repository| repository.find_merge(&branch_names, &commit_sha))
+			.await
+	}
+
+	/// See [`TrackedRepository::first_containing`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the commits cannot be checked
+	pub async fn first_containing(
+		&self,
+		commit_shas: Vec<String>,
+		commit_sha: String,
+	) -> Result<Option<usize>, Error> {
+		self.run(move |repository| repository.first_containing(&commit_shas, &commit_sha))
+			.await
+	}
+
+	/// See [`TrackedRepository::branches_contain_sha`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be checked
+	pub async fn branches_contain_sha(
+		&self,
+		branch_names: Vec<String>,
+		commit_sha: String,
+	) -> Result<Vec<(String, bool)>, Error> {
+		self.run(move |repository| {
+			let results = repository.branches_contain_sha(&branch_names, &commit_sha)?;
+			Ok(results
+				.into_iter()
+				.map(|(name, has_commit)| (name.clone(), has_commit))
+				.collect())
+		})
+		.await
+	}
+
+	/// See [`TrackedRepository::branches_landing_of_sha`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be checked
+	pub async fn branches_landing_of_sha(
+		&self,
+		branch_names: Vec<String>,
+		commit_sha: String,
+	) -> Result<Vec<(String, Option<Landing>)>, Error> {
+		self.run(move |repository| {
+			let results = repository.branches_landing_of_sha(&branch_names, &commit_sha)?;
+			Ok(results
+				.into_iter()
+				.map(|(name, landing)| (name.clone(), landing))
+				.collect())
+		})
+		.await
+	}
+
+	/// See [`TrackedRepository::branches_contain_shas`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be checked
+	pub async fn branches_contain_shas(
+		&self,
+		branch_names: Vec<String>,
+		commit_shas: Vec<String>,
+	) -> Result<Reachability, Error> {
+		self.run(move |repository| {
+			repository.branches_contain_shas(&branch_names, commit_shas.iter().map(String::as_str))
+		})
+		.await
+	}
+
+	/// See [`TrackedRepository::first_tag_containing`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the tags cannot be checked
+	pub async fn first_tag_containing(&self, commit_sha: String) -> Result<Option<Tag>, Error> {
+		self.run(move |repository| repository.first_tag_containing(&commit_sha))
+			.await
+	}
+
+	/// See [`TrackedRepository::branches_backports_of_shas`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the branches cannot be searched
+	pub async fn branches_backports_of_shas(
+		&self,
+		branch_names: Vec<String>,
+		merge_commit_sha: String,
+		commit_shas: Vec<String>,
+		pull_request: Option<u64>,
+	) -> Result<Vec<(String, Option<Backport>)>, Error> {
+		self.run(move |repository| {
+			let results = repository.branches_backports_of_shas(
+				&branch_names,
+				&merge_commit_sha,
+				&commit_shas,
+				pull_request,
+			)?;
+			Ok(results
+				.into_iter()
+				.map(|(name, backport)| (name.clone(), backport))
+				.collect())
+		})
+		.await
+	}
+}
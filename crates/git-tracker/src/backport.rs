@@ -0,0 +1,245 @@
+//! Finding changes that reached a branch as a backport rather than directly
+use crate::{message, Error, Landing, TrackedRepository, CLOCK_SKEW_SECS};
+
+use std::{collections::HashMap, sync::PoisonError};
+
+use git2::{BranchType, Oid, Reference, Repository, Sort};
+use log::{debug, trace};
+
+/// The shortest abbreviated commit ID we'll trust in a cherry-pick trailer
+const MIN_ABBREVIATED_LEN: usize = 7;
+
+/// How many searches we remember before starting over
+const MAX_CACHED_SEARCHES: usize = 10_000;
+
+/// A commit on a branch that carries a change made somewhere else
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backport {
+	/// ID of the backported commit
+	pub commit: Oid,
+	/// The first-parent commit on the branch that brought in the backport
+	pub landing: Option<Landing>,
+	/// The number of the PR the backport was merged in, if it was merged by one
+	pub pull_request: Option<u64>,
+}
+
+/// How far we've searched a branch for backports of a change, and what we found
+#[derive(Clone, Copy, Debug)]
+struct Search {
+	/// The tip of the branch when we searched it
+	tip: Oid,
+	backport: Option<Backport>,
+}
+
+/// Earlier searches for backports, by the merge commit of the change and the branch searched
+///
+/// Branches only move forward, so a search can pick up where the last one stopped
+#[derive(Debug, Default)]
+pub(crate) struct Searches(HashMap<(Oid, String), Search>);
+
+/// Check if a commit message marks it as a backport of any of `commits` or PR `pull_request`
+fn is_backport(message: &str, commits: &[String], pull_request: Option<u64>) -> bool {
+	message::cherry_picked_from(message).any(|picked| {
+		picked.len() >= MIN_ABBREVIATED_LEN && commits.iter().any(|sha| sha.starts_with(picked))
+	}) || pull_request.is_some_and(|number| message::is_backport_of(message, number))
+}
+
+impl TrackedRepository {
+	/// Find backports of a change on multiple branches
+	///
+	/// The change was merged in `merge_commit_sha`, and is made up of `commit_shas`. Commits
+	/// count as backported if they were cherry-picked from either with `git cherry-pick -x`,
+	/// or were merged in a PR marked as a backport of `pull_request`
+	///
+	/// Only history since each branch split off from the merge commit's is searched. Results
+	/// are cached, so later searches only look at what the branches picked up since
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened, a branch cannot be found, or
+	/// commits cannot be read
+	pub fn branches_backports_of_shas<'a>(
+		&self,
+		branch_names: impl IntoIterator<Item = &'a String>,
+		merge_commit_sha: &str,
+		commit_shas: &[String],
+		pull_request: Option<u64>,
+	) -> Result<Vec<(&'a String, Option<Backport>)>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		// we can't have anything of a change we haven't fetched yet
+		let Ok(merge_commit) = repository.find_commit(Oid::from_str(merge_commit_sha)?) else {
+			return Ok(branch_names.into_iter().map(|name| (name, None)).collect());
+		};
+
+		// the merge commit is cherry-picked from, too, when PRs are squashed or rebased
+		let mut picked = commit_shas.to_vec();
+		picked.push(merge_commit_sha.to_string());
+		// backports can't be older than what they're backporting
+		let cutoff = picked
+			.iter()
+			.filter_map(|sha| Oid::from_str(sha).ok())
+			.filter_map(|id| repository.find_commit(id).ok())
+			.map(|commit| commit.time().seconds())
+			.min()
+			.unwrap_or(merge_commit.time().seconds())
+			- CLOCK_SKEW_SECS;
+
+		let mut searches = self
+			.backport_searches
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		if searches.0.len() > MAX_CACHED_SEARCHES {
+			debug!("Forgetting {} backport searches", searches.0.len());
+			searches.0.clear();
+		}
+
+		let mut results = vec![];
+		for branch_name in branch_names {
+			let reference = repository
+				.find_branch(
+					&format!("{}/{branch_name}", self.remote_name),
+					BranchType::Remote,
+				)?
+				.into_reference();
+			let tip = reference.peel_to_commit()?.id();
+
+			let key = (merge_commit.id(), branch_name.clone());
+			let previous = searches.0.get(&key).copied();
+			let backport = match previous {
+				Some(Search {
+					backport: Some(backport),
+					..
+				}) => Some(backport),
+				Some(search) if search.tip == tip => None,
+				_ => {
+					let mut revwalk = repository.revwalk()?;
+					revwalk.set_sorting(Sort::TIME)?;
+					revwalk.push(tip)?;
+					// nothing from before the branch split off can be a backport...
+					if let Ok(base) = repository.merge_base(tip, merge_commit.id()) {
+						revwalk.hide(base)?;
+					}
+					// ...and we've already looked at everything up to where we stopped last time
+					if let Some(search) =
+						previous.filter(|search| repository.find_commit(search.tip).is_ok())
+					{
+						revwalk.hide(search.tip)?;
+					}
+
+					Self::find_backport(
+						&repository,
+						&reference,
+						revwalk,
+						&picked,
+						pull_request,
+						cutoff,
+					)?
+				}
+			};
+
+			searches.0.insert(key, Search { tip, backport });
+			results.push((branch_name, backport));
+		}
+
+		Ok(results)
+	}
+
+	/// Search the commits of `revwalk` for a backport, back to `cutoff`
+	fn find_backport(
+		repository: &Repository,
+		reference: &Reference,
+		revwalk: git2::Revwalk,
+		commit_shas: &[String],
+		pull_request: Option<u64>,
+		cutoff: i64,
+	) -> Result<Option<Backport>, Error> {
+		let branch_name = reference.shorthand().unwrap_or("<branch>");
+		let mut visited = 0;
+		for id in revwalk {
+			let commit = repository.find_commit(id?)?;
+			if commit.time().seconds() < cutoff {
+				break;
+			}
+			visited += 1;
+
+			if !is_backport(
+				commit.message().unwrap_or_default(),
+				commit_shas,
+				pull_request,
+			) {
+				continue;
+			}
+
+			trace!("Found backport {} on {branch_name}", commit.id());
+			let landing = Self::find_landing(repository, reference, commit.id())?;
+			// cherry-picks are usually brought in by a merge commit, which knows the backport PR
+			let pull_request = landing
+				.and_then(|landing| repository.find_commit(landing.commit).ok())
+				.into_iter()
+				.chain([commit.clone()])
+				.find_map(|commit| message::merged_pull_request(commit.message()?));
+
+			return Ok(Some(Backport {
+				commit: commit.id(),
+				landing,
+				pull_request,
+			}));
+		}
+		trace!("Visited {visited} commits on {branch_name} without finding a backport");
+
+		Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::testing::Scratch;
+
+	#[test]
+	fn finds_cherry_picks_since_the_last_search() {
+		let scratch = Scratch::new("backports");
+		let base = scratch.commit(100_000, &[]);
+		let change = scratch.commit(200_000, &[base]);
+		let merge = scratch.commit_with_message(
+			300_000,
+			&[base, change],
+			"Merge pull request #123 from someone/hello",
+		);
+		scratch.branch("master", merge);
+		let release = scratch.commit(250_000, &[base]);
+		scratch.branch("release-24.05", release);
+
+		let tracked = scratch.tracked();
+		let branches = ["release-24.05".to_string()];
+		let search = || {
+			tracked
+				.branches_backports_of_shas(
+					&branches,
+					&merge.to_string(),
+					&[change.to_string()],
+					Some(123),
+				)
+				.unwrap()[0]
+				.1
+		};
+		assert_eq!(search(), None);
+
+		let picked = scratch.commit_with_message(
+			400_000,
+			&[release],
+			&format!("hello: fix\n\n(cherry picked from commit {change})"),
+		);
+		let backport_merge = scratch.commit_with_message(
+			500_000,
+			&[release, picked],
+			"Merge pull request #456 from someone/hello-24.05",
+		);
+		scratch.branch("release-24.05", backport_merge);
+
+		let backport = search().unwrap();
+		assert_eq!(backport.commit, picked);
+		assert_eq!(backport.pull_request, Some(456));
+		assert_eq!(backport.landing.unwrap().commit, backport_merge);
+	}
+}
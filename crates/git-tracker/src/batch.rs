@@ -0,0 +1,217 @@
+//! Answer "which of these branches contain which of these commits" in one walk
+use crate::{Error, CLOCK_SKEW_SECS};
+
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use git2::{Oid, Repository};
+use log::trace;
+
+/// Bitset of branch indices
+type Mask = u64;
+
+/// The most branches we can check in a single walk
+pub const MAX_BRANCHES: usize = Mask::BITS as usize;
+
+/// The results of a batch reachability query
+#[derive(Clone, Debug, Default)]
+pub struct Reachability {
+	/// One walk for every [`MAX_BRANCHES`] branches we checked
+	walks: Vec<Walk>,
+}
+
+impl Reachability {
+	/// Check if a branch contains a commit
+	#[must_use]
+	pub fn contains(&self, commit: Oid, branch: &str) -> bool {
+		self.walks.iter().any(|walk| walk.contains(commit, branch))
+	}
+
+	/// Get every branch that contains a commit
+	pub fn branches_containing(&self, commit: Oid) -> impl Iterator<Item = &String> {
+		self.walks
+			.iter()
+			.flat_map(move |walk| walk.branches_containing(commit))
+	}
+}
+
+impl FromIterator<Walk> for Reachability {
+	fn from_iter<I: IntoIterator<Item = Walk>>(walks: I) -> Self {
+		Self {
+			walks: walks.into_iter().collect(),
+		}
+	}
+}
+
+/// The results of walking the history of up to [`MAX_BRANCHES`] branches
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Walk {
+	/// Names of the branches we checked, in the order of the bits in each [`Mask`]
+	branches: Vec<String>,
+	/// Branches each target commit was found in
+	masks: HashMap<Oid, Mask>,
+}
+
+impl Walk {
+	/// Check if a branch contains a commit
+	fn contains(&self, commit: Oid, branch: &str) -> bool {
+		let Some(index) = self.branches.iter().position(|name| name == branch) else {
+			return false;
+		};
+
+		self.masks
+			.get(&commit)
+			.is_some_and(|mask| mask & (1 << index) != 0)
+	}
+
+	/// Get every branch that contains a commit
+	fn branches_containing(&self, commit: Oid) -> impl Iterator<Item = &String> {
+		let mask = self.masks.get(&commit).copied().unwrap_or_default();
+		self.branches
+			.iter()
+			.enumerate()
+			.filter(move |(index, _)| mask & (1 << index) != 0)
+			.map(|(_, name)| name)
+	}
+}
+
+/// Walk the history of `tips` once, painting each commit with the set of branches that can
+/// reach it, until every commit in `targets` has been found or we're past the point where they
+/// could be
+///
+/// # Errors
+///
+/// Will return [`Err`] if there are more than [`MAX_BRANCHES`] tips or commits can't be read
+pub(crate) fn walk(
+	repository: &Repository,
+	tips: Vec<(String, Oid)>,
+	targets: &[Oid],
+) -> Result<Walk, Error> {
+	if tips.len() > MAX_BRANCHES {
+		return Err(Error::TooManyBranches(tips.len()));
+	}
+
+	let full_mask = if tips.len() == MAX_BRANCHES {
+		Mask::MAX
+	} else {
+		(1 << tips.len()) - 1
+	};
+
+	// Commits we don't have yet can't be in any branch
+	let mut cutoff = i64::MAX;
+	let mut remaining = vec![];
+	for target in targets {
+		if let Ok(commit) = repository.find_commit(*target) {
+			cutoff = cutoff.min(commit.time().seconds() - CLOCK_SKEW_SECS);
+			remaining.push(*target);
+		}
+	}
+
+	let mut masks: HashMap<Oid, Mask> = HashMap::new();
+	// Newest commits first, so most commits are painted by all of their children before they're
+	// visited. Clock skew can still break that, so commits are requeued whenever they gain a branch
+	let mut queue = BinaryHeap::new();
+	let mut branches = Vec::with_capacity(tips.len());
+	for (index, (name, tip)) in tips.into_iter().enumerate() {
+		let commit = repository.find_commit(tip)?;
+		*masks.entry(tip).or_default() |= 1 << index;
+		queue.push((commit.time().seconds(), tip));
+		branches.push(name);
+	}
+
+	let mut visited = 0;
+	while let Some((_, id)) = queue.pop() {
+		if remaining.is_empty() {
+			break;
+		}
+
+		visited += 1;
+		let mask = masks[&id];
+		// Once a target has been painted by every branch, there's nothing left to learn about it
+		remaining.retain(|target| masks.get(target).is_none_or(|mask| *mask != full_mask));
+
+		let commit = repository.find_commit(id)?;
+		for parent in commit.parents() {
+			let parent_mask = masks.entry(parent.id()).or_default();
+			if *parent_mask | mask == *parent_mask {
+				continue;
+			}
+
+			*parent_mask |= mask;
+			let time = parent.time().seconds();
+			if time >= cutoff {
+				queue.push((time, parent.id()));
+			}
+		}
+	}
+	trace!("Visited {visited} commits for {} targets", targets.len());
+
+	let targets: HashSet<_> = targets.iter().collect();
+	masks.retain(|id, _| targets.contains(id));
+
+	Ok(Walk { branches, masks })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::MAX_BRANCHES;
+	use crate::testing::Scratch;
+
+	#[test]
+	fn merge_older_than_its_parents() {
+		let scratch = Scratch::new("skew");
+		let base = scratch.commit(100_000, &[]);
+		let main = scratch.commit(300_000, &[base]);
+		let change = scratch.commit(200_000, &[base]);
+		// committed on a machine with a clock a few days behind
+		let merge = scratch.commit(50_000, &[main, change]);
+		scratch.branch("main", main);
+		scratch.branch("merged", merge);
+
+		let branches = ["main".to_string(), "merged".to_string()];
+		let reachability = scratch
+			.tracked()
+			.branches_contain_shas(
+				&branches,
+				[base, change]
+					.map(|id| id.to_string())
+					.iter()
+					.map(String::as_str),
+			)
+			.unwrap();
+
+		assert!(reachability.contains(change, "merged"));
+		assert!(!reachability.contains(change, "main"));
+		assert!(reachability.contains(base, "main"));
+		assert!(reachability.contains(base, "merged"));
+		assert_eq!(
+			reachability.branches_containing(change).collect::<Vec<_>>(),
+			["merged"]
+		);
+	}
+
+	#[test]
+	fn more_branches_than_fit_in_one_walk() {
+		let scratch = Scratch::new("chunks");
+		let base = scratch.commit(100_000, &[]);
+		let change = scratch.commit(200_000, &[base]);
+
+		// every other branch has the change, so both walks find some
+		let branches = (0..MAX_BRANCHES + 6)
+			.map(|index| format!("branch-{index}"))
+			.collect::<Vec<_>>();
+		for (index, branch) in branches.iter().enumerate() {
+			scratch.branch(branch, if index % 2 == 0 { change } else { base });
+		}
+
+		let change_sha = change.to_string();
+		let reachability = scratch
+			.tracked()
+			.branches_contain_shas(&branches, [change_sha.as_str()])
+			.unwrap();
+
+		let containing = reachability.branches_containing(change).collect::<Vec<_>>();
+		assert_eq!(containing.len(), branches.len() / 2);
+		assert!(reachability.contains(change, &format!("branch-{}", MAX_BRANCHES + 2)));
+		assert!(!reachability.contains(change, &format!("branch-{}", MAX_BRANCHES + 1)));
+	}
+}
@@ -0,0 +1,211 @@
+//! Information about fetches, and staging them for `git`
+use std::{
+	collections::{HashMap, HashSet},
+	time::Duration,
+};
+
+use git2::{Oid, Progress, Repository};
+use log::debug;
+
+/// Where `git` fetches refs to before they're moved into place
+const STAGING_PREFIX: &str = "refs/tracker-staging/";
+
+/// A snapshot of transfer progress during a fetch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FetchProgress {
+	/// Objects the remote is sending us
+	pub total_objects: usize,
+	/// Objects we've received so far
+	pub received_objects: usize,
+	/// Objects we've indexed so far
+	pub indexed_objects: usize,
+	/// Deltas that need to be resolved
+	pub total_deltas: usize,
+	/// Deltas we've resolved so far
+	pub indexed_deltas: usize,
+	/// Bytes we've received so far
+	pub received_bytes: usize,
+}
+
+impl From<Progress<'_>> for FetchProgress {
+	fn from(progress: Progress<'_>) -> Self {
+		Self {
+			total_objects: progress.total_objects(),
+			received_objects: progress.received_objects(),
+			indexed_objects: progress.indexed_objects(),
+			total_deltas: progress.total_deltas(),
+			indexed_deltas: progress.indexed_deltas(),
+			received_bytes: progress.received_bytes(),
+		}
+	}
+}
+
+/// A change to a remote-tracking reference made by a fetch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefUpdate {
+	/// Full name of the reference (i.e., `refs/remotes/origin/master`)
+	pub name: String,
+	/// Where the reference pointed before the fetch, if it existed
+	pub old: Option<Oid>,
+	/// Where the reference points after the fetch, if it still exists
+	pub new: Option<Oid>,
+}
+
+impl RefUpdate {
+	/// Check if this reference was created by the fetch
+	#[must_use]
+	pub fn is_created(&self) -> bool {
+		self.old.is_none()
+	}
+
+	/// Check if this reference was deleted by the fetch
+	#[must_use]
+	pub fn is_deleted(&self) -> bool {
+		self.new.is_none()
+	}
+
+	/// Get the name of the branch this reference tracks (i.e., `master` for
+	/// `refs/remotes/origin/master`)
+	#[must_use]
+	pub fn branch(&self) -> Option<&str> {
+		let (_, branch) = self.name.strip_prefix("refs/remotes/")?.split_once('/')?;
+		Some(branch)
+	}
+}
+
+/// The results of a fetch
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FetchReport {
+	/// References that were created, updated, or deleted
+	pub updates: Vec<RefUpdate>,
+	/// Final transfer statistics
+	///
+	/// These are always zero when using [`crate::CloneMode::Blobless`], as `git` does the
+	/// transfer for us
+	pub stats: FetchProgress,
+	/// How long the fetch took
+	pub duration: Duration,
+	/// Whether another fetch finished while we were waiting, so we didn't fetch ourselves
+	///
+	/// `updates` is empty then, as they were already reported to whoever did fetch
+	pub coalesced: bool,
+}
+
+/// Where each remote-tracking reference of `remote_name` points
+pub(crate) fn snapshot(
+	repository: &Repository,
+	remote_name: &str,
+) -> Result<HashMap<String, Oid>, git2::Error> {
+	let mut tips = HashMap::new();
+	for reference in repository.references_glob(&format!("refs/remotes/{remote_name}/*"))? {
+		let reference = reference?;
+		if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+			tips.insert(name.to_string(), target);
+		}
+	}
+
+	Ok(tips)
+}
+
+/// Compare two [`snapshot`]s
+pub(crate) fn diff(before: &HashMap<String, Oid>, after: &HashMap<String, Oid>) -> Vec<RefUpdate> {
+	let mut updates: Vec<_> = after
+		.iter()
+		.filter(|(name, new)| before.get(*name) != Some(new))
+		.map(|(name, new)| RefUpdate {
+			name: name.clone(),
+			old: before.get(name).copied(),
+			new: Some(*new),
+		})
+		.chain(
+			before
+				.iter()
+				.filter(|(name, _)| !after.contains_key(*name))
+				.map(|(name, old)| RefUpdate {
+					name: name.clone(),
+					old: Some(*old),
+					new: None,
+				}),
+		)
+		.collect();
+	updates.sort_by(|a, b| a.name.cmp(&b.name));
+
+	updates
+}
+
+/// Point the destination of a refspec (i.e., `+refs/heads/master:refs/remotes/origin/master`)
+/// into our staging namespace
+pub(crate) fn staged_refspec(refspec: &str) -> String {
+	match refspec.split_once(':') {
+		Some((source, destination)) => format!(
+			"{source}:{STAGING_PREFIX}{}",
+			destination.strip_prefix("refs/").unwrap_or(destination)
+		),
+		None => refspec.to_string(),
+	}
+}
+
+/// Delete anything left in our staging namespace by a fetch that didn't finish
+pub(crate) fn clear_staging(repository: &Repository) -> Result<(), git2::Error> {
+	let names = repository
+		.references_glob(&format!("{STAGING_PREFIX}*"))?
+		.names()
+		.map(|name| name.map(ToString::to_string))
+		.collect::<Result<Vec<_>, _>>()?;
+	for name in names {
+		repository.find_reference(&name)?.delete()?;
+	}
+
+	Ok(())
+}
+
+/// Move refs fetched into our staging namespace into place
+///
+/// Refs matching the destination of any of `refspecs` that weren't fetched are deleted, like
+/// `git fetch --prune` would
+pub(crate) fn unstage(repository: &Repository, refspecs: &[String]) -> Result<(), git2::Error> {
+	let destinations: Vec<_> = refspecs
+		.iter()
+		.filter_map(|refspec| Some(refspec.split_once(':')?.1))
+		.collect();
+	let fetched_into = |name: &str| {
+		destinations
+			.iter()
+			.any(|destination| match destination.strip_suffix('*') {
+				Some(prefix) => name.starts_with(prefix),
+				None => name == *destination,
+			})
+	};
+
+	let mut staged = vec![];
+	for reference in repository.references_glob(&format!("{STAGING_PREFIX}*"))? {
+		let reference = reference?;
+		if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+			staged.push((name.to_string(), target));
+		}
+	}
+
+	let mut fetched = HashSet::new();
+	for (name, target) in staged {
+		let destination = format!("refs/{}", &name[STAGING_PREFIX.len()..]);
+		repository.reference(&destination, target, true, "fetch")?;
+		repository.find_reference(&name)?.delete()?;
+		fetched.insert(destination);
+	}
+
+	let stale = repository
+		.references()?
+		.names()
+		.map(|name| name.map(ToString::to_string))
+		.filter(|name| {
+			name.as_ref()
+				.map_or(true, |name| fetched_into(name) && !fetched.contains(name))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	for name in stale {
+		debug!("Pruning {name}");
+		repository.find_reference(&name)?.delete()?;
+	}
+
+	Ok(())
+}
@@ -0,0 +1,206 @@
+//! Checking for (and fixing) broken repositories
+use crate::{lock::sibling, Error, TrackedRepository};
+
+use std::{fmt, sync::Arc};
+
+use git2::{Repository, Sort};
+use log::{info, warn};
+
+/// How many commits to read from each branch when looking for corruption
+const COMMITS_TO_CHECK: usize = 1000;
+
+/// Something wrong with a [`TrackedRepository`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Problem {
+	/// The repository can't be opened at all
+	Unopenable(String),
+	/// Our remote doesn't exist
+	MissingRemote,
+	/// Our remote points to a different URL than it should
+	RemoteUrlMismatch {
+		expected: String,
+		actual: Option<String>,
+	},
+	/// Objects we need are missing or can't be read
+	Corrupt(String),
+}
+
+impl Problem {
+	/// Check if the only way to fix this is to start over
+	fn needs_reclone(&self) -> bool {
+		matches!(self, Self::Unopenable(_) | Self::Corrupt(_))
+	}
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Unopenable(why) => write!(f, "repository can't be opened: {why}"),
+			Self::MissingRemote => write!(f, "remote is missing"),
+			Self::RemoteUrlMismatch { expected, actual } => write!(
+				f,
+				"remote points to {} instead of {expected}",
+				actual.as_deref().unwrap_or("<invalid URL>")
+			),
+			Self::Corrupt(why) => write!(f, "repository is corrupt: {why}"),
+		}
+	}
+}
+
+/// Get a useful message out of our [`Error`]
+fn describe(error: &Error) -> String {
+	match error {
+		Error::Git(error) => error.message().to_string(),
+		error => error.to_string(),
+	}
+}
+
+impl TrackedRepository {
+	/// Look for problems that would keep the repository from working
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository can't be locked for reading
+	pub fn verify(&self) -> Result<Vec<Problem>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = match self.open() {
+			Ok(repository) => repository,
+			Err(why) => return Ok(vec![Problem::Unopenable(describe(&why))]),
+		};
+
+		let mut problems = vec![];
+		match repository.find_remote(&self.remote_name) {
+			Ok(remote) => {
+				if remote.url() != Some(&self.remote_url) {
+					problems.push(Problem::RemoteUrlMismatch {
+						expected: self.remote_url.clone(),
+						actual: remote.url().map(ToString::to_string),
+					});
+				}
+			}
+			Err(_) => problems.push(Problem::MissingRemote),
+		}
+
+		if let Err(why) = self.check_objects(&repository) {
+			problems.push(Problem::Corrupt(describe(&why)));
+		}
+
+		Ok(problems)
+	}
+
+	/// Read the most recent commits (and their trees) on each of our branches
+	fn check_objects(&self, repository: &Repository) -> Result<(), Error> {
+		for reference in
+			repository.references_glob(&format!("refs/remotes/{}/*", self.remote_name))?
+		{
+			let reference = reference?;
+			if reference.symbolic_target().is_some() {
+				continue;
+			}
+
+			let mut revwalk = repository.revwalk()?;
+			revwalk.set_sorting(Sort::TIME)?;
+			revwalk.push(reference.peel_to_commit()?.id())?;
+			for id in revwalk.take(COMMITS_TO_CHECK) {
+				repository.find_commit(id?)?.tree()?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Fix any problems found by [`TrackedRepository::verify`]
+	///
+	/// Remotes are fixed in place. Anything worse is fixed by cloning a fresh copy of the
+	/// repository next to the old one, then swapping it in
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository can't be checked or repaired
+	pub fn repair(&self) -> Result<Vec<Problem>, Error> {
+		let problems = self.verify()?;
+		if problems.is_empty() {
+			return Ok(problems);
+		}
+
+		for problem in &problems {
+			warn!(
+				"Found problem with repository at {}: {problem}",
+				self.path.display()
+			);
+		}
+
+		// Nobody should be fetching while we do this
+		std::fs::create_dir_all(&self.path)?;
+		let _guard = self.locks.fetch_exclusive(&self.path)?;
+
+		if problems.iter().any(Problem::needs_reclone) {
+			self.reclone()?;
+			return Ok(problems);
+		}
+
+		let repository = self.open()?;
+		for problem in &problems {
+			match problem {
+				Problem::MissingRemote => {
+					info!("Adding missing remote {}", self.remote_name);
+					self.configure_remote(&repository)?;
+				}
+				Problem::RemoteUrlMismatch { .. } => {
+					info!(
+						"Pointing remote {} to {}",
+						self.remote_name, self.remote_url
+					);
+					repository.remote_set_url(&self.remote_name, &self.remote_url)?;
+				}
+				Problem::Unopenable(_) | Problem::Corrupt(_) => {}
+			}
+		}
+
+		Ok(problems)
+	}
+
+	/// Clone a fresh copy of the repository and swap it in
+	fn reclone(&self) -> Result<(), Error> {
+		let fresh_path = sibling(&self.path, ".tracker-fresh");
+		let old_path = sibling(&self.path, ".tracker-old");
+		for path in [&fresh_path, &old_path] {
+			if path.exists() {
+				std::fs::remove_dir_all(path)?;
+			}
+		}
+
+		info!(
+			"Cloning a fresh copy of the repository to {}",
+			fresh_path.display()
+		);
+		let fresh = Self {
+			path: fresh_path.clone(),
+			locks: Arc::default(),
+			..self.clone()
+		};
+		fresh.clone_repository(None)?;
+
+		{
+			// our lock files aren't in the repository, so everyone else keeps waiting on them
+			// until both renames are done
+			let _guard = self.locks.write(&self.path)?;
+			info!(
+				"Swapping {} into {}",
+				fresh_path.display(),
+				self.path.display()
+			);
+			std::fs::rename(&self.path, &old_path)?;
+			std::fs::rename(&fresh_path, &self.path)?;
+		}
+
+		if let Err(why) = std::fs::remove_dir_all(&old_path) {
+			warn!(
+				"Couldn't remove old repository at {}!\n{why:?}",
+				old_path.display()
+			);
+		}
+
+		Ok(())
+	}
+}
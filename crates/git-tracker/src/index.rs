@@ -0,0 +1,367 @@
+//! An index of merged PRs, built from the history of our branches
+use crate::{lock::sibling, message, Error, Landing, TrackedRepository};
+
+use std::{
+	collections::HashMap,
+	fs,
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+	sync::PoisonError,
+};
+
+use git2::{BranchType, Oid, Repository, Time};
+use log::{debug, info, warn};
+
+/// Suffix of the file the index is kept in, next to the repository so it survives a reclone
+const INDEX_FILE: &str = ".tracker-pull-requests";
+/// First line of the index file, so we know what we're reading
+const INDEX_HEADER: &str = "# git-tracker pull request index v2";
+
+/// A PR found in the history of one of our branches
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergedPullRequest {
+	/// Number of the PR
+	pub number: u64,
+	/// Title of the PR, as recorded in its merge commit
+	pub title: Option<String>,
+	/// The commit the PR was merged in
+	pub commit: Oid,
+	/// Committer time of `commit`
+	pub time: Time,
+	/// The branch the PR was merged into
+	pub branch: String,
+}
+
+/// The first-parent commit that brought a commit into a branch, and the PR it was made for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Merge {
+	/// The branch the commit was merged into
+	pub branch: String,
+	/// Where the commit landed on `branch`
+	pub landing: Landing,
+	/// The PR `landing` was made for, if it was merged by one
+	pub pull_request: Option<u64>,
+	/// Where the commit landed on each of the branches searched that have it, including
+	/// `branch`
+	pub landings: HashMap<String, Landing>,
+}
+
+/// Where a PR was merged
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Entry {
+	commit: Oid,
+	branch: String,
+}
+
+/// PR numbers mapped to the commits they were merged in, along with how far each branch has
+/// been indexed
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+	tips: HashMap<String, Oid>,
+	pull_requests: HashMap<u64, Entry>,
+}
+
+impl Index {
+	/// Read an index file, starting over if it doesn't exist or can't be understood
+	fn load(path: &Path) -> Self {
+		let Ok(contents) = fs::read_to_string(path) else {
+			return Self::default();
+		};
+
+		let mut lines = contents.lines();
+		if lines.next() != Some(INDEX_HEADER) {
+			warn!("Ignoring unknown PR index at {}", path.display());
+			return Self::default();
+		}
+
+		let mut index = Self::default();
+		for line in lines {
+			let mut fields = line.split(' ');
+			match (fields.next(), fields.next(), fields.next(), fields.next()) {
+				(Some("tip"), Some(branch), Some(commit), None) => {
+					if let Ok(commit) = Oid::from_str(commit) {
+						index.tips.insert(branch.to_string(), commit);
+					}
+				}
+				(Some("pr"), Some(number), Some(commit), Some(branch)) => {
+					if let (Ok(number), Ok(commit)) = (number.parse(), Oid::from_str(commit)) {
+						index.pull_requests.insert(
+							number,
+							Entry {
+								commit,
+								branch: branch.to_string(),
+							},
+						);
+					}
+				}
+				_ => warn!("Ignoring malformed line in PR index: {line}"),
+			}
+		}
+
+		index
+	}
+
+	/// Write the index to a file, replacing it all at once
+	fn save(&self, path: &Path) -> std::io::Result<()> {
+		let temporary = sibling(path, ".tmp");
+		let mut writer = BufWriter::new(fs::File::create(&temporary)?);
+		writeln!(writer, "{INDEX_HEADER}")?;
+		for (branch, commit) in &self.tips {
+			writeln!(writer, "tip {branch} {commit}")?;
+		}
+		for (number, entry) in &self.pull_requests {
+			writeln!(writer, "pr {number} {} {}", entry.commit, entry.branch)?;
+		}
+		writer.into_inner()?.sync_all()?;
+
+		fs::rename(temporary, path)
+	}
+
+	/// Index the first-parent history of a branch, back to where we last left off
+	///
+	/// A PR found on more than one branch is recorded as merged into the one with the lowest
+	/// `stage`. Returns how many PRs were added
+	fn update(
+		&mut self,
+		repository: &Repository,
+		branch: &str,
+		tip: Oid,
+		stage: &impl Fn(&str) -> usize,
+	) -> Result<usize, Error> {
+		let last = self.tips.get(branch).copied();
+		if last == Some(tip) {
+			return Ok(0);
+		}
+
+		let mut revwalk = repository.revwalk()?;
+		revwalk.simplify_first_parent()?;
+		revwalk.push(tip)?;
+		// if the branch was force-pushed, we'll just walk it all again
+		if let Some(last) = last.filter(|last| repository.find_commit(*last).is_ok()) {
+			revwalk.hide(last)?;
+		}
+
+		let mut added = 0;
+		for id in revwalk {
+			let commit = repository.find_commit(id?)?;
+			let Some(number) = commit.message().and_then(message::pull_request) else {
+				continue;
+			};
+
+			// the earliest stage a PR is found on is the one it was merged into. everything else
+			// got it from there (or was fast-forwarded to it)
+			let entry = Entry {
+				commit: commit.id(),
+				branch: branch.to_string(),
+			};
+			match self.pull_requests.get(&number) {
+				None => {
+					added += 1;
+					self.pull_requests.insert(number, entry);
+				}
+				Some(existing) if stage(branch) < stage(&existing.branch) => {
+					self.pull_requests.insert(number, entry);
+				}
+				Some(_) => {}
+			}
+		}
+
+		self.tips.insert(branch.to_string(), tip);
+
+		Ok(added)
+	}
+}
+
+impl TrackedRepository {
+	/// Where our index of merged PRs is kept
+	fn index_path(&self) -> PathBuf {
+		sibling(&self.path, INDEX_FILE)
+	}
+
+	/// Bring the index of merged PRs up to date with our branches
+	///
+	/// Only history added since the last update is read, so this is cheap to call after every
+	/// fetch. Returns how many PRs were added
+	///
+	/// Merge commits show up on every branch that got them later, too. `stage` ranks branches
+	/// by how early changes reach them, so the lowest ranked one is recorded as where a PR was
+	/// merged
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened, its history cannot be read, or
+	/// the index cannot be saved
+	pub fn update_pull_request_index(&self, stage: impl Fn(&str) -> usize) -> Result<usize, Error> {
+		// the index is written under the lock, too, so it always matches the refs we read
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let branches = self.tracked_branches_in(&repository)?;
+		let path = self.index_path();
+		let mut cached = self
+			.pull_request_index
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner);
+		let index = cached.get_or_insert_with(|| Index::load(&path));
+		let tips = index.tips.clone();
+
+		let mut added = 0;
+		for branch in &branches {
+			let reference = repository
+				.find_reference(&format!("refs/remotes/{}/{branch}", self.remote_name))?;
+			let tip = reference.peel_to_commit()?.id();
+			added += index.update(&repository, branch, tip, &stage)?;
+		}
+
+		// forget about branches we don't track anymore
+		index.tips.retain(|branch, _| branches.contains(branch));
+
+		debug!("Indexed {added} new PRs");
+		if added > 0 {
+			info!("PR index now has {} PRs", index.pull_requests.len());
+		}
+		// nothing to write unless we've moved on (or the file went missing, like after a reclone)
+		if added > 0 || index.tips != tips || !path.exists() {
+			index.save(&path)?;
+		}
+
+		Ok(added)
+	}
+
+	/// Look up a PR in the index of merged PRs
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened or the merge commit cannot be read
+	pub fn find_merged_pull_request(
+		&self,
+		number: u64,
+	) -> Result<Option<MergedPullRequest>, Error> {
+		let entry = {
+			let mut cached = self
+				.pull_request_index
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner);
+			let index = cached.get_or_insert_with(|| Index::load(&self.index_path()));
+			index.pull_requests.get(&number).cloned()
+		};
+		let Some(entry) = entry else {
+			return Ok(None);
+		};
+
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let commit = repository.find_commit(entry.commit)?;
+
+		Ok(Some(MergedPullRequest {
+			number,
+			title: commit
+				.message()
+				.and_then(message::pull_request_title)
+				.map(ToString::to_string),
+			commit: entry.commit,
+			time: commit.time(),
+			branch: entry.branch,
+		}))
+	}
+
+	/// Find the merge that first brought a commit into any of some branches, and the PR it
+	/// belongs to, along with where the commit landed on the rest of them
+	///
+	/// Changes are merged into one branch, then flow into others through merges of whole
+	/// branches (i.e., `staging-next` into `master`). Those always come later, so the earliest
+	/// landing is the one that matters
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened, an [`Oid`] could not be resolved
+	/// from the commit SHA, a branch cannot be found, or history cannot be walked
+	pub fn find_merge<'a>(
+		&self,
+		branch_names: impl IntoIterator<Item = &'a String>,
+		commit_sha: &str,
+	) -> Result<Option<Merge>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let commit = Oid::from_str(commit_sha)?;
+
+		let mut landings = HashMap::new();
+		let mut earliest: Option<(&String, Landing)> = None;
+		for branch_name in branch_names {
+			let branch = repository.find_branch(
+				&format!("{}/{branch_name}", self.remote_name),
+				BranchType::Remote,
+			)?;
+
+			let Some(landing) = Self::find_landing(&repository, &branch.into_reference(), commit)?
+			else {
+				continue;
+			};
+
+			if earliest.is_none_or(|(_, earliest)| landing.time.seconds() < earliest.time.seconds())
+			{
+				earliest = Some((branch_name, landing));
+			}
+			landings.insert(branch_name.clone(), landing);
+		}
+
+		let Some((branch, landing)) = earliest else {
+			return Ok(None);
+		};
+		let pull_request = repository
+			.find_commit(landing.commit)?
+			.message()
+			.and_then(message::pull_request);
+
+		Ok(Some(Merge {
+			branch: branch.clone(),
+			landing,
+			pull_request,
+			landings,
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::testing::Scratch;
+
+	#[test]
+	fn finds_the_earliest_merge_and_every_landing() {
+		let scratch = Scratch::new("merges");
+		let base = scratch.commit(100_000, &[]);
+		let change = scratch.commit(200_000, &[base]);
+		let merge = scratch.commit_with_message(
+			300_000,
+			&[base, change],
+			"Merge pull request #123 from someone/hello",
+		);
+		scratch.branch("staging", merge);
+		let promoted = scratch.commit_with_message(
+			400_000,
+			&[base, merge],
+			"Merge branch 'staging' into master",
+		);
+		scratch.branch("master", promoted);
+
+		let branches = ["master".to_string(), "staging".to_string()];
+		let found = scratch
+			.tracked()
+			.find_merge(&branches, &change.to_string())
+			.unwrap()
+			.unwrap();
+		assert_eq!(found.branch, "staging");
+		assert_eq!(found.landing.commit, merge);
+		assert_eq!(found.pull_request, Some(123));
+		assert_eq!(found.landings["staging"].commit, merge);
+		assert_eq!(found.landings["master"].commit, promoted);
+
+		let missing = scratch.commit(500_000, &[base]);
+		assert_eq!(
+			scratch
+				.tracked()
+				.find_merge(&branches, &missing.to_string())
+				.unwrap(),
+			None
+		);
+	}
+}
@@ -1,25 +1,96 @@
 //! Library for helping you track commits and branches in a Git repository
-use std::path::PathBuf;
+use std::{
+	path::PathBuf,
+	process::Command,
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+mod backport;
+mod batch;
+mod fetch;
+mod health;
+mod index;
+mod lock;
+mod message;
+mod pattern;
+mod tags;
+#[cfg(test)]
+mod testing;
+mod tips;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncTrackedRepository;
+pub use backport::Backport;
+pub use batch::{Reachability, MAX_BRANCHES};
+pub use fetch::{FetchProgress, FetchReport, RefUpdate};
+pub use git2::Oid;
+pub use health::Problem;
+pub use index::{Merge, MergedPullRequest};
+pub use tags::Tag;
+pub use tips::Tip;
 
 use git2::{
-	BranchType, FetchOptions, FetchPrune, Oid, Reference, RemoteCallbacks, RemoteUpdateFlags,
-	Repository,
+	BranchType, Commit, Direction, ErrorCode, FetchOptions, FetchPrune, Reference, Remote,
+	RemoteCallbacks, RemoteUpdateFlags, Repository, Time,
 };
-use log::{debug, info, trace};
+use lock::{FetchTurn, Locks};
+use log::{debug, info, trace, warn};
+use pattern::BranchFilter;
 
 /// Used when logging Git transfer progress
 const INCREMENT_TO_LOG: i32 = 5;
 
+/// How far (in seconds) a commit's time may be skewed from the commits that brought it in
+pub(crate) const CLOCK_SKEW_SECS: i64 = 60 * 60 * 24; // 1 day
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("libgit2 error")]
 	Git(#[from] git2::Error),
 	#[error("i/o error")]
 	IOError(#[from] std::io::Error),
+	#[error("can't check {0} branches at once (the limit is {MAX_BRANCHES})")]
+	TooManyBranches(usize),
+	#[error("invalid branch pattern")]
+	Pattern(#[from] glob::PatternError),
+	#[error("`{0}` matches more than one object")]
+	AmbiguousSha(String),
+	#[error("git exited unsuccessfully ({0})")]
+	GitCommand(std::process::ExitStatus),
+	#[cfg(feature = "tokio")]
+	#[error("blocking task failed")]
+	Join(#[from] tokio::task::JoinError),
+}
+
+/// How much of the remote's history to keep locally
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd, Ord)]
+pub enum CloneMode {
+	/// Every object
+	#[default]
+	Full,
+	/// Only commits and trees. Blobs are fetched lazily by `git` when something needs them
+	///
+	/// libgit2 doesn't support partial clones, so this requires `git` to be in `PATH`
+	Blobless,
+}
+
+/// The first-parent commit on a branch that brought in another commit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Landing {
+	/// ID of the commit
+	pub commit: Oid,
+	/// Committer time of the commit. For fast-forwarded branches, this is when the commit was
+	/// made on the branch they follow
+	pub time: Time,
 }
 
 /// Helper struct for tracking Git objects
-#[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+///
+/// Clones share the same locks, so fetches and reads through any of them are coordinated
+#[derive(Clone, Debug)]
 pub struct TrackedRepository {
 	/// Path to repository
 	path: PathBuf,
@@ -27,6 +98,21 @@ pub struct TrackedRepository {
 	remote_url: String,
 	/// Name of the remote referring to `remote_url`
 	remote_name: String,
+	/// Names of (or glob patterns matching) the branches to fetch. All branches are fetched
+	/// when this is empty
+	branches: Vec<String>,
+	/// Narrows down the remote's branches matching `branches` before we fetch them
+	branch_filter: Option<BranchFilter>,
+	/// How much of the remote we keep
+	clone_mode: CloneMode,
+	/// Whether to fetch tags, too
+	tags: bool,
+	/// Coordination between fetches and reads
+	locks: Arc<Locks>,
+	/// Our index of merged PRs, once it has been read from disk
+	pull_request_index: Arc<Mutex<Option<index::Index>>>,
+	/// Where we've already looked for backports
+	backport_searches: Arc<Mutex<backport::Searches>>,
 }
 
 impl TrackedRepository {
@@ -36,7 +122,231 @@ impl TrackedRepository {
 			path,
 			remote_url,
 			remote_name,
+			branches: Vec::new(),
+			branch_filter: None,
+			clone_mode: CloneMode::default(),
+			tags: false,
+			locks: Arc::default(),
+			pull_request_index: Arc::default(),
+			backport_searches: Arc::default(),
+		}
+	}
+
+	/// Fetch every tag of the remote, too
+	#[must_use]
+	pub fn with_tags(mut self, tags: bool) -> Self {
+		self.tags = tags;
+		self
+	}
+
+	/// Use a different [`CloneMode`]
+	#[must_use]
+	pub fn with_clone_mode(mut self, clone_mode: CloneMode) -> Self {
+		self.clone_mode = clone_mode;
+		self
+	}
+
+	/// Only fetch the given branches
+	///
+	/// These can be exact names, or glob patterns matching many branches (i.e., `nixos-*` or
+	/// `release-2?.??`). Patterns are expanded against the remote's branches on every fetch
+	#[must_use]
+	pub fn with_branches(mut self, branches: Vec<String>) -> Self {
+		self.branches = branches;
+		self
+	}
+
+	/// Only fetch the branches `filter` keeps out of the remote's branches matching ours
+	///
+	/// The remote's branches have to be listed for this, which costs an extra connection per
+	/// fetch. Branches that are filtered out are pruned, too
+	#[must_use]
+	pub fn with_branch_filter(
+		mut self,
+		filter: impl Fn(Vec<String>) -> Vec<String> + Send + Sync + 'static,
+	) -> Self {
+		self.branch_filter = Some(BranchFilter(Arc::new(filter)));
+		self
+	}
+
+	/// Refspecs for fetching our branches into remote-tracking branches
+	///
+	/// When the `advertised` branches of the remote are known, our branches are expanded using
+	/// them, so only ones it has (and we kept) are fetched. Otherwise, patterns Git can't
+	/// express in a refspec are skipped
+	fn refspecs(&self, advertised: Option<&[String]>) -> Result<Vec<String>, Error> {
+		let mut branches = vec![];
+		for branch in &self.branches {
+			let Some(advertised) = advertised else {
+				if !pattern::needs_expansion(branch) {
+					branches.push(branch.as_str());
+				}
+				continue;
+			};
+
+			let pattern = glob::Pattern::new(branch)?;
+			let before = branches.len();
+			branches.extend(
+				advertised
+					.iter()
+					.filter(|name| pattern.matches(name))
+					.map(String::as_str),
+			);
+			if branches.len() == before {
+				debug!(
+					"Skipping {branch}, which {} doesn't have or was filtered out",
+					self.remote_name
+				);
+			}
+		}
+		// patterns can overlap
+		branches.sort_unstable();
+		branches.dedup();
+
+		let mut refspecs: Vec<_> = branches
+			.into_iter()
+			.map(|branch| {
+				format!(
+					"+refs/heads/{branch}:refs/remotes/{}/{branch}",
+					self.remote_name
+				)
+			})
+			.collect();
+
+		if self.tags {
+			// the remote's default refspec is only used when we don't give it any
+			if self.branches.is_empty() {
+				refspecs.push(format!("+refs/heads/*:refs/remotes/{}/*", self.remote_name));
+			}
+			refspecs.push("+refs/tags/*:refs/tags/*".to_string());
+		}
+
+		Ok(refspecs)
+	}
+
+	/// Get the names of the branches the remote has that we'd fetch, if any of ours need to be
+	/// expanded, we're filtering them, or we're fetching with `git`
+	///
+	/// Unlike libgit2, `git` fails the whole fetch when a branch we ask for doesn't exist
+	fn advertised_branches(&self, remote: &mut Remote) -> Result<Option<Vec<String>>, Error> {
+		if self.clone_mode != CloneMode::Blobless
+			&& self.branch_filter.is_none()
+			&& !self
+				.branches
+				.iter()
+				.any(|branch| pattern::needs_expansion(branch))
+		{
+			return Ok(None);
+		}
+
+		debug!("Listing branches of {}", self.remote_name);
+		remote.connect(Direction::Fetch)?;
+		let branches = remote
+			.list()?
+			.iter()
+			.filter_map(|head| head.name().strip_prefix("refs/heads/"))
+			.map(ToString::to_string)
+			.collect();
+		remote.disconnect()?;
+
+		self.advertised(branches).map(Some)
+	}
+
+	/// Narrow the remote's branches down to the ones matching ours that we keep
+	fn advertised(&self, branches: Vec<String>) -> Result<Vec<String>, Error> {
+		let patterns = self
+			.branches
+			.iter()
+			.map(|branch| glob::Pattern::new(branch))
+			.collect::<Result<Vec<_>, _>>()?;
+		let branches = branches
+			.into_iter()
+			.filter(|branch| patterns.is_empty() || pattern::matches_any(&patterns, branch))
+			.collect();
+
+		Ok(match &self.branch_filter {
+			Some(BranchFilter(filter)) => filter(branches),
+			None => branches,
+		})
+	}
+
+	/// Delete remote-tracking branches of ours that the remote no longer has, or that we've
+	/// filtered out
+	///
+	/// Git only prunes refs matching the refspecs it was given, which expanded patterns and
+	/// skipped branches aren't part of
+	fn prune_unadvertised(
+		&self,
+		repository: &Repository,
+		advertised: &[String],
+	) -> Result<(), Error> {
+		let patterns = self
+			.branches
+			.iter()
+			.map(|branch| glob::Pattern::new(branch))
+			.collect::<Result<Vec<_>, _>>()?;
+		let prefix = format!("refs/remotes/{}/", self.remote_name);
+
+		for reference in repository.references_glob(&format!("{prefix}*"))? {
+			let mut reference = reference?;
+			let Some(branch) = reference.name().and_then(|name| name.strip_prefix(&prefix)) else {
+				continue;
+			};
+
+			if pattern::matches_any(&patterns, branch)
+				&& !advertised.iter().any(|name| name == branch)
+			{
+				debug!("Pruning {branch}");
+				reference.delete()?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Get the names of every remote-tracking branch matching our branches
+	///
+	/// This is where glob patterns are expanded for everything but fetching, so it only knows
+	/// about branches as of the last fetch
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened, its references cannot be read, or
+	/// a branch pattern is invalid
+	pub fn tracked_branches(&self) -> Result<Vec<String>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+
+		self.tracked_branches_in(&repository)
+	}
+
+	/// List our branches in an opened repository, assuming refs are already locked for reading
+	fn tracked_branches_in(&self, repository: &Repository) -> Result<Vec<String>, Error> {
+		let patterns = self
+			.branches
+			.iter()
+			.map(|branch| glob::Pattern::new(branch))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let prefix = format!("refs/remotes/{}/", self.remote_name);
+		let mut branches = vec![];
+		for reference in repository.references_glob(&format!("{prefix}*"))? {
+			let reference = reference?;
+			if reference.symbolic_target().is_some() {
+				continue;
+			}
+
+			let Some(branch) = reference.name().and_then(|name| name.strip_prefix(&prefix)) else {
+				continue;
+			};
+
+			if patterns.is_empty() || pattern::matches_any(&patterns, branch) {
+				branches.push(branch.to_string());
+			}
 		}
+		branches.sort();
+
+		Ok(branches)
 	}
 
 	/// Open a [`Repository`]
@@ -49,31 +359,100 @@ impl TrackedRepository {
 		Ok(Repository::open(&self.path)?)
 	}
 
+	/// Find the full ID of a commit from a (possibly abbreviated) SHA
+	///
+	/// Returns [`None`] if no commit matches
+	///
+	/// # Errors
+	///
+	/// Will return [`Error::AmbiguousSha`] if more than one object matches `sha`, or [`Err`] if
+	/// the repository cannot be opened or `sha` isn't valid
+	pub fn resolve_commit(&self, sha: &str) -> Result<Option<Oid>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+
+		let result = match repository.find_commit_by_prefix(sha) {
+			Ok(commit) => Ok(Some(commit.id())),
+			Err(why) if why.code() == ErrorCode::Ambiguous => {
+				Err(Error::AmbiguousSha(sha.to_string()))
+			}
+			Err(why) if why.code() == ErrorCode::NotFound => Ok(None),
+			Err(why) => Err(why.into()),
+		};
+
+		result
+	}
+
+	/// Add our remote to a [`Repository`]
+	fn configure_remote(&self, repository: &Repository) -> Result<(), Error> {
+		debug!("Adding remote {} for {}", self.remote_name, self.remote_url);
+		// Make sure the remote itself knows to only fetch our branches, too. Anything that needs
+		// to be expanded is left out, and handled on each fetch
+		let refspecs = self.refspecs(None)?;
+		if let Some((first, rest)) = refspecs.split_first() {
+			repository.remote_with_fetch(&self.remote_name, &self.remote_url, first)?;
+			for refspec in rest {
+				repository.remote_add_fetch(&self.remote_name, refspec)?;
+			}
+		} else {
+			repository.remote(&self.remote_name, &self.remote_url)?;
+		}
+
+		if self.clone_mode == CloneMode::Blobless {
+			// NOTE: `git` would normally bump `core.repositoryformatversion` to 1 when it sets
+			// this up itself, but libgit2 refuses to open v1 repositories with extensions it
+			// doesn't know about. `git` still honors `extensions.partialclone` in v0 repositories
+			// for compatibility, so we configure it all ahead of time
+			debug!("Configuring {} as a promisor remote", self.remote_name);
+			let mut config = repository.config()?;
+			config.set_bool(&format!("remote.{}.promisor", self.remote_name), true)?;
+			config.set_str(
+				&format!("remote.{}.partialclonefilter", self.remote_name),
+				"blob:none",
+			)?;
+			config.set_str("extensions.partialclone", &self.remote_name)?;
+		}
+
+		Ok(())
+	}
+
 	/// Clone a (small) fresh copy of your repository
 	///
 	/// # Errors
 	///
 	/// Will return [`Err`] if the path, repository, or remote cannot be created
-	pub fn clone_repository(&self) -> Result<(), Error> {
+	pub fn clone_repository(
+		&self,
+		progress: Option<&mut dyn FnMut(&FetchProgress)>,
+	) -> Result<FetchReport, Error> {
 		// Setup a bare repository to save space
 		info!("Creating repository at {}", self.path.display());
 		std::fs::create_dir_all(&self.path)?;
+		let guard = match self.locks.fetch(&self.path)? {
+			FetchTurn::Ours(guard) => guard,
+			FetchTurn::Coalesced(report) => return Ok(report),
+		};
 		let repository = Repository::init_bare(&self.path)?;
 
-		debug!("Adding remote {} for {}", self.remote_name, self.remote_url,);
-		repository.remote(&self.remote_name, &self.remote_url)?;
-		self.fetch()?;
+		self.configure_remote(&repository)?;
 
-		Ok(())
+		let report = self.fetch_locked(progress)?;
+		guard.finish(&report);
+
+		Ok(report)
 	}
 
 	#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-	fn fetch_options<'a>() -> FetchOptions<'a> {
+	fn fetch_options(mut progress: Option<&mut dyn FnMut(&FetchProgress)>) -> FetchOptions<'_> {
 		let mut rc = RemoteCallbacks::new();
 
 		// Log transfer progress
 		let mut current_percentage = 1;
 		rc.transfer_progress(move |stats| {
+			if let Some(progress) = progress.as_mut() {
+				progress(&FetchProgress::from(stats.to_owned()));
+			}
+
 			if stats.received_objects() == stats.total_objects() {
 				// HACK: Avoid dividing by zero
 				// I have no idea how this can ever be zero but ok
@@ -110,16 +489,6 @@ impl TrackedRepository {
 			true
 		});
 
-		// Log ref updates
-		rc.update_tips(|refname, orig_oid, new_oid| {
-			if orig_oid.is_zero() {
-				info!("[new]   {:20} {}", new_oid, refname);
-			} else {
-				info!("[updated]   {:10}..{:10} {}", orig_oid, new_oid, refname);
-			}
-			true
-		});
-
 		let mut fetch_options = FetchOptions::new();
 		// Make sure we prune on fetch
 		fetch_options.prune(FetchPrune::On).remote_callbacks(rc);
@@ -132,23 +501,127 @@ impl TrackedRepository {
 	/// # Errors
 	///
 	/// Will return [`Err`] if the repository cannot be opened, the remote cannot be found, the
-	/// refs cannot be fetched, or the tips of the refs cannot be updated
-	pub fn fetch(&self) -> Result<(), Error> {
+	/// refs cannot be fetched, the tips of the refs cannot be updated, or `git` fails when using
+	/// [`CloneMode::Blobless`]
+	pub fn fetch(
+		&self,
+		progress: Option<&mut dyn FnMut(&FetchProgress)>,
+	) -> Result<FetchReport, Error> {
+		// Concurrent fetches would only be fighting over the same refs
+		let guard = match self.locks.fetch(&self.path)? {
+			FetchTurn::Ours(guard) => guard,
+			FetchTurn::Coalesced(report) => return Ok(report),
+		};
+
+		let report = self.fetch_locked(progress)?;
+		guard.finish(&report);
+
+		Ok(report)
+	}
+
+	/// Fetch the tracked remote, assuming we're the only one doing so
+	fn fetch_locked(
+		&self,
+		progress: Option<&mut dyn FnMut(&FetchProgress)>,
+	) -> Result<FetchReport, Error> {
+		let timer = Instant::now();
 		let repository = self.open()?;
+		let before = fetch::snapshot(&repository, &self.remote_name)?;
 
 		let mut remote = repository.find_remote(&self.remote_name)?;
+		let advertised = self.advertised_branches(&mut remote)?;
+		let refspecs = self.refspecs(advertised.as_deref())?;
 
-		info!("Fetching repository");
-		remote.download(&[] as &[&str], Some(&mut Self::fetch_options()))?;
-		remote.disconnect()?;
+		let stats = if refspecs.is_empty() && !self.branches.is_empty() {
+			// An empty list would have us fetch the remote's default refspecs instead
+			warn!("None of our branches exist on {}", self.remote_name);
+			let _guard = self.locks.write(&self.path)?;
+			if let Some(advertised) = &advertised {
+				self.prune_unadvertised(&repository, advertised)?;
+			}
+
+			FetchProgress::default()
+		} else if self.clone_mode == CloneMode::Blobless {
+			// `git` updates refs as it goes, so it fetches into a namespace of its own. Readers
+			// only have to wait for those refs to be moved into place
+			fetch::clear_staging(&repository)?;
+			self.fetch_with_git(&refspecs)?;
+
+			let _guard = self.locks.write(&self.path)?;
+			fetch::unstage(&repository, &refspecs)?;
+			if let Some(advertised) = &advertised {
+				self.prune_unadvertised(&repository, advertised)?;
+			}
+
+			FetchProgress::default()
+		} else {
+			info!("Fetching repository");
+			remote.download(&refspecs, Some(&mut Self::fetch_options(progress)))?;
+			let stats = FetchProgress::from(remote.stats());
+
+			debug!("Updating tips");
+			let _guard = self.locks.write(&self.path)?;
+			remote.update_tips(
+				None,
+				RemoteUpdateFlags::UPDATE_FETCHHEAD,
+				git2::AutotagOption::None,
+				None,
+			)?;
+			remote.prune(None)?;
+			remote.disconnect()?;
+			if let Some(advertised) = &advertised {
+				self.prune_unadvertised(&repository, advertised)?;
+			}
+
+			stats
+		};
 
-		debug!("Updating tips");
-		remote.update_tips(
-			None,
-			RemoteUpdateFlags::UPDATE_FETCHHEAD,
-			git2::AutotagOption::None,
-			None,
-		)?;
+		let after = fetch::snapshot(&repository, &self.remote_name)?;
+		let updates = fetch::diff(&before, &after);
+		for update in &updates {
+			match (update.old, update.new) {
+				(None, Some(new)) => info!("[new]   {new:20} {}", update.name),
+				(Some(old), Some(new)) => info!("[updated]   {old:10}..{new:10} {}", update.name),
+				(Some(old), None) => info!("[deleted]   {old:20} {}", update.name),
+				(None, None) => {}
+			}
+		}
+
+		Ok(FetchReport {
+			updates,
+			stats,
+			duration: timer.elapsed(),
+			coalesced: false,
+		})
+	}
+
+	/// Fetch the tracked remote into our staging namespace using the `git` CLI, for modes
+	/// libgit2 doesn't support
+	///
+	/// See [`fetch::unstage`] for moving the fetched refs into place
+	fn fetch_with_git(&self, refspecs: &[String]) -> Result<(), Error> {
+		info!("Fetching repository with git");
+		let status = Command::new("git")
+			.arg("-C")
+			.arg(&self.path)
+			.args([
+				"fetch",
+				"--no-tags",
+				"--filter=blob:none",
+				// don't let it update our remote-tracking branches behind our back
+				"--refmap=",
+				&self.remote_name,
+			])
+			.args(
+				refspecs
+					.iter()
+					.map(|refspec| fetch::staged_refspec(refspec)),
+			)
+			.status()?;
+
+		if !status.success() {
+			return Err(Error::GitCommand(status));
+		}
 
 		Ok(())
 	}
@@ -194,6 +667,7 @@ impl TrackedRepository {
 		branch_names: impl IntoIterator<Item = &'a String>,
 		commit_sha: &str,
 	) -> Result<Vec<(&'a String, bool)>, Error> {
+		let _guard = self.locks.read(&self.path)?;
 		let repository = self.open()?;
 		let commit = Oid::from_str(commit_sha)?;
 
@@ -211,4 +685,312 @@ impl TrackedRepository {
 
 		Ok(results)
 	}
+
+	/// Find when a [`Reference`] first contained a given Git object
+	///
+	/// This walks the first-parent history of the reference to find the commit that actually
+	/// brought in `commit` (i.e., a merge commit, or `commit` itself if it was pushed directly)
+	///
+	/// Branches that are only ever fast-forwarded to another branch (like nixpkgs' channels)
+	/// share that branch's first-parent history. Their landing is the one from the branch they
+	/// follow, so its time is when `commit` was merged there, not when this branch advanced to it
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the reference or commit cannot be resolved, or the relation between
+	/// commits cannot be resolved
+	pub fn find_landing(
+		repository: &Repository,
+		reference: &Reference,
+		commit: Oid,
+	) -> Result<Option<Landing>, Error> {
+		if !Self::ref_contains_object(repository, reference, commit)? {
+			return Ok(None);
+		}
+
+		let contains = |candidate: &Commit| -> Result<bool, Error> {
+			Ok(candidate.id() == commit
+				|| repository.graph_descendant_of(candidate.id(), commit)?)
+		};
+
+		// Containment is monotonic along first-parent history (once an ancestor doesn't have the
+		// commit, none of its ancestors will either), so we can binary search it. Commits from
+		// before our target was made can't contain it, so that's where we stop collecting
+		let cutoff = repository.find_commit(commit)?.time().seconds() - CLOCK_SKEW_SECS;
+		let mut chain = vec![reference.peel_to_commit()?];
+		while let Some(last) = chain.last() {
+			if last.id() == commit {
+				break;
+			}
+
+			match last.parent(0) {
+				Ok(parent) if parent.time().seconds() >= cutoff => chain.push(parent),
+				_ => break,
+			}
+		}
+		trace!(
+			"Searching {} first-parent commits for {commit}",
+			chain.len()
+		);
+
+		let mut low = 0;
+		let mut high = chain.len();
+		while low < high {
+			let middle = low + (high - low) / 2;
+			if contains(&chain[middle])? {
+				low = middle + 1;
+			} else {
+				high = middle;
+			}
+		}
+
+		// If the whole chain contains it, our commit must have a *really* skewed clock. Keep
+		// walking until we find the boundary
+		let exhausted = low == chain.len();
+		// `chain[0]` is the tip of the reference, which we already know contains the commit
+		let mut landing = chain.swap_remove(low - 1);
+		if exhausted {
+			while let Ok(parent) = landing.parent(0) {
+				if !contains(&parent)? {
+					break;
+				}
+				landing = parent;
+			}
+		}
+
+		Ok(Some(Landing {
+			commit: landing.id(),
+			time: landing.time(),
+		}))
+	}
+
+	/// Find when multiple [`Reference`]s first contained a commit SHA
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if an [`Oid`] could not be resolved from the commit SHA
+	/// or when it can't be determined when a reference first contained a commit
+	pub fn branches_landing_of_sha<'a>(
+		&self,
+		branch_names: impl IntoIterator<Item = &'a String>,
+		commit_sha: &str,
+	) -> Result<Vec<(&'a String, Option<Landing>)>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let commit = Oid::from_str(commit_sha)?;
+
+		let mut results = vec![];
+		for branch_name in branch_names {
+			let branch = repository.find_branch(
+				&format!("{}/{branch_name}", self.remote_name),
+				BranchType::Remote,
+			)?;
+
+			let landing = Self::find_landing(&repository, &branch.into_reference(), commit)?;
+			results.push((branch_name, landing));
+		}
+
+		Ok(results)
+	}
+
+	/// Find the first of some commit SHAs that contains another
+	///
+	/// `commit_shas` have to be in the order a branch pointed to them, so that once one of them
+	/// contains `commit_sha`, every one after it does too. Commits we don't have count as not
+	/// containing it
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened or an [`Oid`] could not be resolved
+	/// from `commit_sha`
+	pub fn first_containing(
+		&self,
+		commit_shas: &[String],
+		commit_sha: &str,
+	) -> Result<Option<usize>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let commit = Oid::from_str(commit_sha)?;
+
+		let first = commit_shas.partition_point(|sha| {
+			let Ok(candidate) = Oid::from_str(sha) else {
+				return true;
+			};
+
+			candidate != commit
+				&& !repository
+					.graph_descendant_of(candidate, commit)
+					.unwrap_or_default()
+		});
+
+		Ok((first < commit_shas.len()).then_some(first))
+	}
+
+	/// Check if multiple [`Reference`]s contain multiple commit SHAs
+	///
+	/// Unlike [`TrackedRepository::branches_contain_sha`], every pair is answered from a single
+	/// walk of the repository's history for every [`MAX_BRANCHES`] branches. Commit SHAs that
+	/// are invalid or that we don't have (yet) are skipped, and no branch contains them
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if a branch cannot be found or history cannot be walked
+	pub fn branches_contain_shas<'a, 'b>(
+		&self,
+		branch_names: impl IntoIterator<Item = &'a String>,
+		commit_shas: impl IntoIterator<Item = &'b str>,
+	) -> Result<Reachability, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let mut commits = vec![];
+		for commit_sha in commit_shas {
+			match Oid::from_str(commit_sha).and_then(|commit| repository.find_commit(commit)) {
+				Ok(commit) => commits.push(commit.id()),
+				Err(why) => debug!("Skipping commit {commit_sha}: {}", why.message()),
+			}
+		}
+
+		let mut tips = vec![];
+		for branch_name in branch_names {
+			let branch = repository.find_branch(
+				&format!("{}/{branch_name}", self.remote_name),
+				BranchType::Remote,
+			)?;
+			tips.push((branch_name.clone(), branch.get().peel_to_commit()?.id()));
+		}
+
+		tips.chunks(MAX_BRANCHES)
+			.map(|tips| batch::walk(&repository, tips.to_vec(), &commits))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{testing::Scratch, TrackedRepository};
+
+	use std::path::PathBuf;
+
+	use git2::Oid;
+
+	#[test]
+	fn refspecs_only_include_kept_branches() {
+		let tracked = TrackedRepository::new(
+			PathBuf::from("nixpkgs"),
+			"https://example.com/nixpkgs".to_string(),
+			"origin".to_string(),
+		)
+		.with_branches(vec!["master".to_string(), "nixos-*".to_string()])
+		.with_branch_filter(|mut branches| {
+			branches.retain(|branch| branch != "nixos-23.11");
+			branches
+		});
+		let advertised = ["master", "nixos-24.05", "nixos-23.11", "staging"]
+			.map(ToString::to_string)
+			.to_vec();
+		let kept = tracked.advertised(advertised).unwrap();
+
+		assert_eq!(
+			tracked.refspecs(Some(&kept)).unwrap(),
+			[
+				"+refs/heads/master:refs/remotes/origin/master",
+				"+refs/heads/nixos-24.05:refs/remotes/origin/nixos-24.05",
+			]
+		);
+	}
+
+	/// Find when `origin/main` first contained a commit
+	fn landing(scratch: &Scratch, commit: Oid) -> Option<Oid> {
+		let branches = ["main".to_string()];
+		let landings = scratch
+			.tracked()
+			.branches_landing_of_sha(&branches, &commit.to_string())
+			.unwrap();
+
+		landings[0].1.map(|landing| landing.commit)
+	}
+
+	#[test]
+	fn lands_on_first_parent_chain() {
+		let scratch = Scratch::new("landing-chain");
+		let first = scratch.commit(100_000, &[]);
+		let second = scratch.commit(200_000, &[first]);
+		let third = scratch.commit(300_000, &[second]);
+		scratch.branch("main", third);
+
+		assert_eq!(landing(&scratch, first), Some(first));
+		assert_eq!(landing(&scratch, second), Some(second));
+		assert_eq!(landing(&scratch, third), Some(third));
+	}
+
+	#[test]
+	fn lands_with_merge_of_second_parent() {
+		let scratch = Scratch::new("landing-merge");
+		let base = scratch.commit(100_000, &[]);
+		let main = scratch.commit(200_000, &[base]);
+		let change = scratch.commit(150_000, &[base]);
+		let merge = scratch.commit(300_000, &[main, change]);
+		let tip = scratch.commit(400_000, &[merge]);
+		let elsewhere = scratch.commit(500_000, &[base]);
+		scratch.branch("main", tip);
+
+		assert_eq!(landing(&scratch, change), Some(merge));
+		assert_eq!(landing(&scratch, main), Some(main));
+		assert_eq!(landing(&scratch, elsewhere), None);
+	}
+
+	#[test]
+	fn lands_despite_clock_skew() {
+		let scratch = Scratch::new("landing-skew");
+		let base = scratch.commit(100_000, &[]);
+		let main = scratch.commit(200_000, &[base]);
+		// committed on a machine with a clock weeks ahead
+		let change = scratch.commit(2_000_000, &[base]);
+		let merge = scratch.commit(300_000, &[main, change]);
+		let tip = scratch.commit(400_000, &[merge]);
+		scratch.branch("main", tip);
+
+		assert_eq!(landing(&scratch, change), Some(merge));
+	}
+
+	#[test]
+	fn finds_first_containing_commit() {
+		let scratch = Scratch::new("first-containing");
+		let base = scratch.commit(100_000, &[]);
+		let change = scratch.commit(200_000, &[base]);
+		let after = scratch.commit(300_000, &[change]);
+		let tips = [
+			"0000000000000000000000000000000000000001".to_string(),
+			base.to_string(),
+			change.to_string(),
+			after.to_string(),
+		];
+
+		let repository = scratch.tracked();
+		let first = |commit: Oid| {
+			repository
+				.first_containing(&tips, &commit.to_string())
+				.unwrap()
+		};
+		assert_eq!(first(base), Some(1));
+		assert_eq!(first(change), Some(2));
+		assert_eq!(first(after), Some(3));
+		assert_eq!(first(scratch.commit(400_000, &[after])), None);
+	}
+
+	#[test]
+	fn skips_commits_it_cannot_find() {
+		let scratch = Scratch::new("contain-shas");
+		let base = scratch.commit(100_000, &[]);
+		scratch.branch("main", base);
+
+		let branches = ["main".to_string()];
+		let missing = "0000000000000000000000000000000000000001";
+		let reachability = scratch
+			.tracked()
+			.branches_contain_shas(&branches, [missing, "not a sha", &base.to_string()])
+			.unwrap();
+		assert!(reachability.contains(base, "main"));
+		assert!(!reachability.contains(Oid::from_str(missing).unwrap(), "main"));
+	}
 }
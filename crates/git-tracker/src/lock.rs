@@ -0,0 +1,156 @@
+//! Coordination between everything touching a repository, in and out of this process
+use crate::FetchReport;
+
+use std::{
+	fs::{File, OpenOptions},
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+	},
+};
+
+// NOTE: newer versions of `std` have their own (inherent) `File::lock*()` methods, so we call
+// these fully qualified to make sure we're always using the same implementation
+use fs4::fs_std::FileExt;
+use log::trace;
+
+// Lock files live next to the repository rather than in it, so they stay put while a broken
+// repository is swapped out for a fresh clone
+
+/// Suffix of the file locked while refs are being read or updated
+const REFS_LOCK_FILE: &str = ".tracker-refs.lock";
+/// Suffix of the file locked while a fetch is running
+const FETCH_LOCK_FILE: &str = ".tracker-fetch.lock";
+
+/// Locks shared by every clone of a [`crate::TrackedRepository`]
+#[derive(Debug, Default)]
+pub(crate) struct Locks {
+	/// Held for reading while we look at refs, and for writing while we update them
+	refs: RwLock<()>,
+	/// Held for the duration of a fetch, storing the result of the last successful one
+	fetch: Mutex<Option<FetchReport>>,
+	/// How many fetches have completed successfully
+	fetches: AtomicU64,
+}
+
+/// Proof that nobody is updating refs
+pub(crate) struct ReadGuard<'a> {
+	_guard: RwLockReadGuard<'a, ()>,
+	_file: File,
+}
+
+/// Proof that nobody is reading or updating refs
+pub(crate) struct WriteGuard<'a> {
+	_guard: RwLockWriteGuard<'a, ()>,
+	_file: File,
+}
+
+/// Proof that nobody else is fetching
+pub(crate) struct FetchGuard<'a> {
+	locks: &'a Locks,
+	last: MutexGuard<'a, Option<FetchReport>>,
+	_file: File,
+}
+
+impl FetchGuard<'_> {
+	/// Record the result of a successful fetch for anyone who was waiting on us
+	pub(crate) fn finish(mut self, report: &FetchReport) {
+		*self.last = Some(report.clone());
+		self.locks.fetches.fetch_add(1, Ordering::SeqCst);
+	}
+}
+
+/// The outcome of waiting to fetch
+pub(crate) enum FetchTurn<'a> {
+	/// It's our turn to fetch
+	Ours(FetchGuard<'a>),
+	/// Someone else fetched while we were waiting, and already reported what changed
+	Coalesced(FetchReport),
+}
+
+/// Get a path next to `path`, with a suffix added to its name
+pub(crate) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+	let mut name = path.file_name().unwrap_or_default().to_os_string();
+	name.push(suffix);
+	path.with_file_name(name)
+}
+
+/// Open (and create, if needed) the lock file of a repository
+fn lock_file(repository: &Path, suffix: &str) -> std::io::Result<File> {
+	OpenOptions::new()
+		.create(true)
+		.truncate(false)
+		.write(true)
+		.open(sibling(repository, suffix))
+}
+
+impl Locks {
+	/// Wait for any ref updates to finish, and keep them from starting
+	pub(crate) fn read(&self, repository: &Path) -> std::io::Result<ReadGuard<'_>> {
+		let guard = self.refs.read().unwrap_or_else(PoisonError::into_inner);
+		let file = lock_file(repository, REFS_LOCK_FILE)?;
+		FileExt::lock_shared(&file)?;
+
+		Ok(ReadGuard {
+			_guard: guard,
+			_file: file,
+		})
+	}
+
+	/// Wait for any readers to finish, and keep new ones from starting
+	pub(crate) fn write(&self, repository: &Path) -> std::io::Result<WriteGuard<'_>> {
+		let guard = self.refs.write().unwrap_or_else(PoisonError::into_inner);
+		let file = lock_file(repository, REFS_LOCK_FILE)?;
+		FileExt::lock_exclusive(&file)?;
+
+		Ok(WriteGuard {
+			_guard: guard,
+			_file: file,
+		})
+	}
+
+	/// Wait for our turn to fetch
+	///
+	/// If another fetch in this process finished while we were waiting, there's no point in
+	/// fetching again right away. Its [`FetchReport`] is returned instead, without the updates
+	/// it already reported
+	pub(crate) fn fetch(&self, repository: &Path) -> std::io::Result<FetchTurn<'_>> {
+		let seen = self.fetches.load(Ordering::SeqCst);
+		let last = self.fetch.lock().unwrap_or_else(PoisonError::into_inner);
+		if self.fetches.load(Ordering::SeqCst) != seen {
+			if let Some(report) = last.as_ref() {
+				trace!("Another fetch finished while we were waiting, reusing its results");
+				return Ok(FetchTurn::Coalesced(FetchReport {
+					updates: Vec::new(),
+					coalesced: true,
+					..report.clone()
+				}));
+			}
+		}
+
+		Ok(FetchTurn::Ours(self.fetch_with(repository, last)?))
+	}
+
+	/// Wait for our turn to fetch, even if someone else just did
+	pub(crate) fn fetch_exclusive(&self, repository: &Path) -> std::io::Result<FetchGuard<'_>> {
+		let last = self.fetch.lock().unwrap_or_else(PoisonError::into_inner);
+		self.fetch_with(repository, last)
+	}
+
+	fn fetch_with<'a>(
+		&'a self,
+		repository: &Path,
+		last: MutexGuard<'a, Option<FetchReport>>,
+	) -> std::io::Result<FetchGuard<'a>> {
+		// Another process may be fetching into the same repository, too
+		let file = lock_file(repository, FETCH_LOCK_FILE)?;
+		FileExt::lock_exclusive(&file)?;
+
+		Ok(FetchGuard {
+			locks: self,
+			last,
+			_file: file,
+		})
+	}
+}
@@ -0,0 +1,191 @@
+//! Reading information out of commit messages
+
+/// The trailer `git cherry-pick -x` adds to a commit
+const CHERRY_PICK_TRAILER: &str = "(cherry picked from commit ";
+
+/// Find the number of the PR a merge commit was made for, from a message like
+/// `Merge pull request #123 from owner/branch`
+pub(crate) fn merged_pull_request(message: &str) -> Option<u64> {
+	let rest = message.strip_prefix("Merge pull request #")?;
+	let end = rest.find(|c: char| !c.is_ascii_digit())?;
+
+	rest[..end].parse().ok()
+}
+
+/// Find the number of the PR a squash-merged commit came from, from a subject like
+/// `hello: 1.0 -> 2.0 (#123)`
+pub(crate) fn squashed_pull_request(message: &str) -> Option<u64> {
+	let subject = message.lines().next()?.trim_end();
+	let rest = subject.strip_suffix(')')?;
+	let start = rest.rfind("(#")?;
+
+	rest[start + 2..].parse().ok()
+}
+
+/// Find the number of the PR a commit on a branch's first-parent history came from
+pub(crate) fn pull_request(message: &str) -> Option<u64> {
+	merged_pull_request(message).or_else(|| squashed_pull_request(message))
+}
+
+/// Find the title of the PR a commit came from
+///
+/// GitHub puts it in the body of merge commits, and in the subject of squashed ones
+pub(crate) fn pull_request_title(message: &str) -> Option<&str> {
+	if merged_pull_request(message).is_some() {
+		return message
+			.lines()
+			.skip(1)
+			.map(str::trim)
+			.find(|line| !line.is_empty());
+	}
+
+	let subject = message.lines().next()?.trim_end();
+	let end = subject.rfind(" (#")?;
+
+	Some(&subject[..end])
+}
+
+/// Find the commits a commit was cherry-picked from
+pub(crate) fn cherry_picked_from(message: &str) -> impl Iterator<Item = &str> {
+	message.lines().filter_map(|line| {
+		line.trim()
+			.strip_prefix(CHERRY_PICK_TRAILER)?
+			.strip_suffix(')')
+	})
+}
+
+/// Find the branch a PR was merged from, from a message like
+/// `Merge pull request #123 from owner/branch`
+fn merged_branch(message: &str) -> Option<&str> {
+	let subject = message.lines().next()?;
+	merged_pull_request(subject)?;
+	let (_, branch) = subject.split_once(" from ")?.1.split_once('/')?;
+
+	Some(branch.trim())
+}
+
+/// Check if a commit message is for a backport of PR `number`
+///
+/// This matches merges of the branches made by nixpkgs' backport action
+/// (`backport-123-to-release-24.05`), and subjects in the format of its PR titles that end with
+/// the original PR (`[Backport release-24.05] hello: 1.0 -> 2.0 (#123)`)
+pub(crate) fn is_backport_of(message: &str, number: u64) -> bool {
+	let from_action = merged_branch(message).is_some_and(|branch| {
+		branch
+			.strip_prefix(&format!("backport-{number}-to-"))
+			.is_some_and(|target| !target.is_empty())
+	});
+	let titled = message.lines().next().is_some_and(|subject| {
+		subject.starts_with("[Backport ") && subject.contains(&format!("(#{number})"))
+	});
+
+	from_action || titled
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn finds_merged_pull_requests() {
+		let message = "Merge pull request #123 from someone/hello\n\nhello: 1.0 -> 2.0";
+
+		assert_eq!(merged_pull_request(message), Some(123));
+		assert_eq!(pull_request(message), Some(123));
+		assert_eq!(
+			merged_pull_request("Merge pull request #abc from x/y"),
+			None
+		);
+		assert_eq!(
+			merged_pull_request("Merge branch 'master' into staging-next"),
+			None
+		);
+	}
+
+	#[test]
+	fn finds_squashed_pull_requests() {
+		assert_eq!(squashed_pull_request("hello: 1.0 -> 2.0 (#123)"), Some(123));
+		assert_eq!(
+			squashed_pull_request("hello: 1.0 -> 2.0 (#123)  \n\nbody (#456)"),
+			Some(123)
+		);
+		assert_eq!(pull_request("hello: 1.0 -> 2.0 (#123)"), Some(123));
+		assert_eq!(squashed_pull_request("hello: 1.0 -> 2.0"), None);
+		assert_eq!(squashed_pull_request("hello: (#123) 1.0 -> 2.0"), None);
+		assert_eq!(squashed_pull_request("hello: 1.0 -> 2.0 (#12a)"), None);
+	}
+
+	#[test]
+	fn finds_pull_request_titles() {
+		assert_eq!(
+			pull_request_title(
+				"Merge pull request #123 from someone/hello\n\n  hello: 1.0 -> 2.0\n"
+			),
+			Some("hello: 1.0 -> 2.0")
+		);
+		assert_eq!(
+			pull_request_title("hello: 1.0 -> 2.0 (#123)"),
+			Some("hello: 1.0 -> 2.0")
+		);
+		assert_eq!(
+			pull_request_title("Merge pull request #123 from someone/hello"),
+			None
+		);
+		assert_eq!(pull_request_title("hello: 1.0 -> 2.0"), None);
+	}
+
+	#[test]
+	fn finds_cherry_picked_commits() {
+		let message = "hello: 1.0 -> 2.0
+
+(cherry picked from commit 0123456789abcdef0123456789abcdef01234567)
+  (cherry picked from commit fedcba9)
+Not (cherry picked from commit 1111111)";
+
+		assert_eq!(
+			cherry_picked_from(message).collect::<Vec<_>>(),
+			["0123456789abcdef0123456789abcdef01234567", "fedcba9"]
+		);
+		assert_eq!(cherry_picked_from("hello: 1.0 -> 2.0").count(), 0);
+	}
+
+	#[test]
+	fn recognizes_backport_action_merges() {
+		let message = "Merge pull request #456 from NixOS/backport-123-to-release-24.05
+
+[Backport release-24.05] hello: 1.0 -> 2.0";
+
+		assert!(is_backport_of(message, 123));
+		assert!(!is_backport_of(message, 12));
+		assert!(!is_backport_of(message, 456));
+	}
+
+	#[test]
+	fn recognizes_backport_titles() {
+		assert!(is_backport_of(
+			"[Backport release-24.05] hello: 1.0 -> 2.0 (#123)",
+			123
+		));
+		assert!(is_backport_of(
+			"[Backport release-24.05] hello: 1.0 -> 2.0 (#123) (#456)",
+			123
+		));
+		assert!(!is_backport_of(
+			"[Backport release-24.05] hello: 1.0 -> 2.0 (#1234)",
+			123
+		));
+	}
+
+	#[test]
+	fn ignores_messages_that_only_mention_backports() {
+		assert!(!is_backport_of("hello: backport fix from #123", 123));
+		assert!(!is_backport_of(
+			"Merge pull request #456 from someone/backport-123-to-",
+			123
+		));
+		assert!(!is_backport_of(
+			"hello: 1.0 -> 2.0\n\n[Backport release-24.05] (#123)",
+			123
+		));
+	}
+}
@@ -0,0 +1,27 @@
+//! Glob patterns for selecting branches
+use std::{fmt, sync::Arc};
+
+use glob::Pattern;
+
+/// Narrows down the branches matching our patterns to the ones worth fetching
+#[derive(Clone)]
+pub(crate) struct BranchFilter(pub(crate) Arc<dyn Fn(Vec<String>) -> Vec<String> + Send + Sync>);
+
+impl fmt::Debug for BranchFilter {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("BranchFilter")
+	}
+}
+
+/// Check if a branch pattern can't be used in a refspec as-is
+///
+/// Refspecs only understand a single `*`, so anything else has to be matched against the
+/// remote's branches ourselves
+pub(crate) fn needs_expansion(pattern: &str) -> bool {
+	pattern.contains(['?', '[', ']']) || pattern.matches('*').count() > 1
+}
+
+/// Check if a branch name matches any of `patterns`
+pub(crate) fn matches_any(patterns: &[Pattern], name: &str) -> bool {
+	patterns.iter().any(|pattern| pattern.matches(name))
+}
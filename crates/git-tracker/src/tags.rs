@@ -0,0 +1,64 @@
+//! Finding the releases a commit made it into
+use crate::{Error, TrackedRepository, CLOCK_SKEW_SECS};
+
+use git2::{Oid, Time};
+use log::trace;
+
+/// A tag pointing to a commit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+	/// Name of the tag (i.e., `24.05`)
+	pub name: String,
+	/// ID of the commit the tag points to
+	pub commit: Oid,
+	/// Committer time of the commit the tag points to
+	pub time: Time,
+}
+
+impl TrackedRepository {
+	/// Find the earliest tag containing a commit
+	///
+	/// Tags are only fetched when enabled with [`TrackedRepository::with_tags`]
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened, an [`Oid`] could not be resolved
+	/// from the commit SHA, or the relation between commits cannot be resolved
+	pub fn first_tag_containing(&self, commit_sha: &str) -> Result<Option<Tag>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+		let commit = Oid::from_str(commit_sha)?;
+		let Ok(target) = repository.find_commit(commit) else {
+			return Ok(None);
+		};
+		// tags made before the commit existed can't contain it
+		let cutoff = target.time().seconds() - CLOCK_SKEW_SECS;
+
+		let mut candidates = vec![];
+		for reference in repository.references_glob("refs/tags/*")? {
+			let reference = reference?;
+			let (Some(name), Ok(tagged)) = (reference.shorthand(), reference.peel_to_commit())
+			else {
+				continue;
+			};
+
+			if tagged.time().seconds() >= cutoff {
+				candidates.push(Tag {
+					name: name.to_string(),
+					commit: tagged.id(),
+					time: tagged.time(),
+				});
+			}
+		}
+		candidates.sort_by_key(|tag| (tag.time.seconds(), tag.name.clone()));
+
+		trace!("Checking {} tags for commit {commit_sha}", candidates.len());
+		for tag in candidates {
+			if tag.commit == commit || repository.graph_descendant_of(tag.commit, commit)? {
+				return Ok(Some(tag));
+			}
+		}
+
+		Ok(None)
+	}
+}
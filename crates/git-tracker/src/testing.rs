@@ -0,0 +1,75 @@
+//! Repositories to test against
+use crate::TrackedRepository;
+
+use std::{fs, path::PathBuf};
+
+use git2::{Oid, Repository, Signature, Time};
+
+/// A bare repository in a temporary directory, removed when dropped
+pub(crate) struct Scratch {
+	/// Directory holding the repository, along with anything we keep next to it
+	directory: PathBuf,
+	pub(crate) repository: Repository,
+}
+
+impl Scratch {
+	pub(crate) fn new(name: &str) -> Self {
+		let directory =
+			std::env::temp_dir().join(format!("git-tracker-{name}-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&directory);
+		let repository = Repository::init_bare(directory.join("repository")).unwrap();
+
+		Self {
+			directory,
+			repository,
+		}
+	}
+
+	/// Make an empty commit at `time`
+	pub(crate) fn commit(&self, time: i64, parents: &[Oid]) -> Oid {
+		self.commit_with_message(time, parents, &time.to_string())
+	}
+
+	/// Make an empty commit at `time`, with a message
+	pub(crate) fn commit_with_message(&self, time: i64, parents: &[Oid], message: &str) -> Oid {
+		let signature = Signature::new("test", "test@example.com", &Time::new(time, 0)).unwrap();
+		let tree = self.repository.treebuilder(None).unwrap().write().unwrap();
+		let tree = self.repository.find_tree(tree).unwrap();
+		let parents = parents
+			.iter()
+			.map(|parent| self.repository.find_commit(*parent).unwrap())
+			.collect::<Vec<_>>();
+
+		self.repository
+			.commit(
+				None,
+				&signature,
+				&signature,
+				message,
+				&tree,
+				&parents.iter().collect::<Vec<_>>(),
+			)
+			.unwrap()
+	}
+
+	/// Point `origin/<name>` at a commit
+	pub(crate) fn branch(&self, name: &str, commit: Oid) {
+		self.repository
+			.reference(&format!("refs/remotes/origin/{name}"), commit, true, "test")
+			.unwrap();
+	}
+
+	pub(crate) fn tracked(&self) -> TrackedRepository {
+		TrackedRepository::new(
+			self.directory.join("repository"),
+			String::new(),
+			"origin".to_string(),
+		)
+	}
+}
+
+impl Drop for Scratch {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.directory);
+	}
+}
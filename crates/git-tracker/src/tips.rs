@@ -0,0 +1,47 @@
+//! Where branches currently are
+use crate::{Error, TrackedRepository};
+
+use git2::{Oid, Time};
+
+/// The commit a branch currently points to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tip {
+	/// Name of the branch (i.e., `nixos-unstable`)
+	pub branch: String,
+	/// ID of the commit
+	pub commit: Oid,
+	/// Committer time of the commit
+	pub time: Time,
+}
+
+impl TrackedRepository {
+	/// Find the commits a set of branches currently point to
+	///
+	/// Branches we don't have are left out
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the repository cannot be opened or a branch's commit cannot be
+	/// found
+	pub fn branch_tips(&self, branch_names: &[String]) -> Result<Vec<Tip>, Error> {
+		let _guard = self.locks.read(&self.path)?;
+		let repository = self.open()?;
+
+		let mut tips = vec![];
+		for branch in branch_names {
+			let ref_name = format!("refs/remotes/{}/{branch}", self.remote_name);
+			let Ok(reference) = repository.find_reference(&ref_name) else {
+				continue;
+			};
+
+			let commit = reference.peel_to_commit()?;
+			tips.push(Tip {
+				branch: branch.clone(),
+				commit: commit.id(),
+				time: commit.time(),
+			});
+		}
+
+		Ok(tips)
+	}
+}
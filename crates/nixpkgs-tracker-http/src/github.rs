@@ -1,4 +1,4 @@
-use super::{Error, PullRequest};
+use super::{Commit, Error, PullRequest};
 
 use std::future::Future;
 
@@ -18,6 +18,20 @@ pub trait Ext {
 		repo_name: &str,
 		id: u64,
 	) -> impl Future<Output = Result<PullRequest, Error>> + Send;
+
+	/// GET `/repos/{repo_owner}/{repo_name}/pulls/{id}/commits`
+	///
+	/// Only the first 100 commits are returned
+	///
+	/// # Errors
+	///
+	/// Will return [`Err`] if the commits cannot be found
+	fn pull_request_commits(
+		&self,
+		repo_owner: &str,
+		repo_name: &str,
+		id: u64,
+	) -> impl Future<Output = Result<Vec<Commit>, Error>> + Send;
 }
 
 impl Ext for super::Client {
@@ -37,4 +51,21 @@ impl Ext for super::Client {
 
 		Ok(pull_request)
 	}
+
+	async fn pull_request_commits(
+		&self,
+		repo_owner: &str,
+		repo_name: &str,
+		id: u64,
+	) -> Result<Vec<Commit>, Error> {
+		let url = format!("{GITHUB_API}/repos/{repo_owner}/{repo_name}/pulls/{id}/commits");
+
+		let request = self.get(&url).query(&[("per_page", "100")]).build()?;
+		trace!("Making GET request to `{}`", request.url());
+		let response = self.execute(request).await?;
+		response.error_for_status_ref()?;
+		let commits: Vec<Commit> = response.json().await?;
+
+		Ok(commits)
+	}
 }
@@ -9,6 +9,20 @@ pub struct PullRequest {
 	pub merged: bool,
 	pub merged_at: Option<String>,
 	pub merge_commit_sha: Option<String>,
+	pub base: Base,
+}
+
+/// The branch a [`PullRequest`] was opened against
+#[derive(Clone, Debug, Deserialize)]
+pub struct Base {
+	#[serde(rename = "ref")]
+	pub branch: String,
+}
+
+/// Item of `/repos/{owner}/{repo}/pulls/{pull_number}/commits` for Github's api
+#[derive(Clone, Debug, Deserialize)]
+pub struct Commit {
+	pub sha: String,
 }
 
 /// `/random_teawie` for the teawieAPI
@@ -44,6 +44,9 @@ in
       wantedBy = [ "multi-user.target" ];
       after = [ "network.target" ];
 
+      # for `BOT_NIXPKGS_CLONE_MODE=blobless`
+      path = [ pkgs.git ];
+
       script = ''
         ${getExe cfg.package}
       '';